@@ -4,6 +4,9 @@ use gmeta::{InOut, Metadata};
 use gstd::{prelude::*, ActorId};
 use scale_info::TypeInfo;
 
+pub type MilestoneId = u32;
+pub type TransactionId = u64;
+
 pub struct ProgramMetadata;
 
 impl Metadata for ProgramMetadata {
@@ -15,30 +18,218 @@ impl Metadata for ProgramMetadata {
    type State = Escrow;
 }
 
-#[derive(Encode, Decode, TypeInfo)]
+#[derive(Default, Encode, Decode, TypeInfo)]
 pub struct InitEscrow {
    pub seller: ActorId,
    pub buyer: ActorId,
    pub price: u128,
+   /// Hash of the off-chain terms of the deal.
+   /// The buyer must echo it on every deposit.
+   pub terms_hash: [u8; 32],
+   /// The tamagotchi sold through the escrow.
+   /// The seller must approve the escrow with `TmgAction::Approve`
+   /// and the ownership is delivered to the buyer on deposit.
+   pub tamagotchi_id: Option<ActorId>,
+   /// Number of blocks the seller has to deliver the goods after the deposit.
+   /// Once it's passed the buyer can take the funds back.
+   pub delivery_period: u32,
+   /// Third party resolving disputes between the buyer and the seller.
+   pub arbiter: Option<ActorId>,
+   /// Arbiter's share of the resolved funds, in basis points.
+   pub arbiter_fee_bps: u16,
+   /// Number of blocks after the shipment the buyer has to confirm the delivery
+   /// or open a dispute. Once it's passed the funds are released to the seller.
+   pub auto_release_period: Option<u32>,
+   /// Parts of the deal paid separately. Their amounts must sum to the price.
+   /// If empty, the whole price is released at once.
+   pub milestones: Vec<Milestone>,
+   pub currency: Currency,
+   pub platform_fee: Option<PlatformFee>,
+   /// Lets a group of buyers purchase the item together.
+   /// Only native deals without milestones or a tamagotchi can be crowdfunded.
+   pub crowdfunding: Option<Crowdfunding>,
+   /// Pays the seller in equal parts, one per period.
+   /// The price is deposited up front and must be divisible by the number of periods.
+   /// The deposit also pays the gas for every release.
+   pub subscription: Option<Subscription>,
+   /// Registers the program creating the escrow as the factory
+   /// allowed to send actions on behalf of the parties.
+   pub from_factory: bool,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct Subscription {
+   /// Number of blocks between releases.
+   pub period: u32,
+   pub periods: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct Crowdfunding {
+   pub contributors: Contributors,
+   /// Number of blocks to collect the price. Once it's passed
+   /// the contributions can be refunded.
+   pub funding_period: u32,
+   /// Number of contributors who must confirm the delivery to release the funds.
+   pub quorum: u32,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum Contributors {
+   /// Only the listed buyers contribute, each up to their share.
+   /// The shares must sum to the price.
+   Shares(Vec<(ActorId, u128)>),
+   /// Anyone can contribute any amount until the price is reached.
+   Open,
+}
+
+/// Fee taken by the escrow service from every payout to the seller.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct PlatformFee {
+   pub recipient: ActorId,
+   pub fee_bps: u16,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum Currency {
+   /// Funds are attached to messages as value.
+   Native,
+   /// Funds are transferred by the sharded fungible token contract.
+   /// The buyer must approve the escrow to spend the price beforehand.
+   FungibleToken(ActorId),
+}
+
+impl Default for Currency {
+   fn default() -> Self {
+      Self::Native
+   }
+}
+
+/// Fungible token transfer that is sent but not completed yet.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct FtTransfer {
+   pub from: ActorId,
+   pub to: ActorId,
+   pub amount: u128,
+   pub kind: TransferKind,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum TransferKind {
+   Deposit,
+   FundMilestone(MilestoneId),
+   Payout,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct Milestone {
+   pub description_hash: [u8; 32],
+   pub amount: u128,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum MilestoneStatus {
+   Pending,
+   Funded,
+   Released,
+   Refunded,
 }
 
 #[derive(Encode, Decode, TypeInfo)]
 pub enum EscrowAction {
-   Deposit,
+   Deposit {
+      terms_hash: [u8; 32],
+   },
    ConfirmDelivery,
+   Refund,
+   Cancel,
+   OpenDispute,
+   Resolve {
+      to_seller: u128,
+      to_buyer: u128,
+   },
+   MarkShipped {
+      tracking_hash: [u8; 32],
+   },
+   /// Sent by the escrow itself as a delayed message.
+   AutoRelease,
+   FundMilestone(MilestoneId),
+   ConfirmMilestone(MilestoneId),
+   /// Resends the interrupted fungible token transfer.
+   Continue(TransactionId),
+   /// Claims the funds owed to the sender.
+   Withdraw,
+   /// Sent by the escrow itself as a delayed message once per period.
+   ReleasePeriod,
+   /// Returns the unreleased periods to the buyer.
+   CancelSubscription,
+   /// Replies with the current state of the deal.
+   State,
+   /// Sent by the factory with the account it acts for.
+   OnBehalf {
+      account: ActorId,
+      action: Box<EscrowAction>,
+   },
 }
 
 #[derive(Encode, Decode, TypeInfo)]
 pub enum EscrowEvent {
    FundsDeposited,
-   DeliveryConfirmed,
+   FundsPartiallyDeposited { remaining: u128 },
+   ChangeReturned(u128),
+   DeliveryConfirmed {
+      fee: u128,
+      net_to_seller: u128,
+   },
+   TamagotchiDelivered(ActorId),
+   TamagotchiNotDelivered(ActorId),
+   Refunded,
+   Cancelled,
+   DisputeOpened(ActorId),
+   Resolved {
+      to_seller: u128,
+      to_buyer: u128,
+      fee: u128,
+      platform_fee: u128,
+   },
+   Shipped,
+   AutoReleased,
+   MilestoneFunded(MilestoneId),
+   MilestoneReleased(MilestoneId),
+   DepositFailed,
+   TransferCompleted(TransactionId),
+   TransferFailed(TransactionId),
+   Withdrawn(u128),
+   ConfirmationRecorded {
+      confirmations: u32,
+      quorum: u32,
+   },
+   SubscriptionStarted,
+   PeriodReleased(u32),
+   SubscriptionCancelled {
+      refunded: u128,
+   },
+   State(EscrowState),
 }
 
-#[derive(Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
 pub enum EscrowState {
    AwaitingPayment,
+   /// The deposit is made and the tamagotchi is being transferred.
+   /// Other actions are rejected until the delivery is settled.
+   Delivering,
    AwaitingDelivery,
+   Shipped,
+   /// The subscription is paid and the periods are being released.
+   Subscribed,
+   /// The deal is done, but the seller hasn't withdrawn the proceeds yet
+   /// or the token payout to the seller is waiting for `Continue`.
+   AwaitingWithdrawal,
    Closed,
+   Refunded,
+   Cancelled,
+   Disputed,
+   Resolved,
 }
 
 impl Default for EscrowState {
@@ -52,5 +243,74 @@ pub struct Escrow {
    pub seller: ActorId,
    pub buyer: ActorId,
    pub price: u128,
+   pub terms_hash: [u8; 32],
+   pub deposited: u128,
    pub state: EscrowState,
+   pub tamagotchi_id: Option<ActorId>,
+   pub delivery_period: u32,
+   pub delivery_deadline: Option<u32>,
+   pub tracking_hash: Option<[u8; 32]>,
+   pub shipped_at: Option<u32>,
+   pub arbiter: Option<ActorId>,
+   pub arbiter_fee_bps: u16,
+   pub auto_release_period: Option<u32>,
+   pub release_block: Option<u32>,
+   pub milestones: Vec<(Milestone, MilestoneStatus)>,
+   pub currency: Currency,
+   pub transaction_id: TransactionId,
+   pub transactions: BTreeMap<TransactionId, FtTransfer>,
+   pub platform_fee: Option<PlatformFee>,
+   pub fee: u128,
+   pub net_to_seller: u128,
+   pub owed: BTreeMap<ActorId, u128>,
+   pub crowdfunding: Option<Crowdfunding>,
+   pub funding_deadline: Option<u32>,
+   pub contributions: BTreeMap<ActorId, u128>,
+   pub confirmations: BTreeSet<ActorId>,
+   pub subscription: Option<Subscription>,
+   pub periods_paid: u32,
+   pub next_release: Option<u32>,
+   pub factory: Option<ActorId>,
 }
+
+/// Overview of the deal returned by the `summary` metafunction.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct EscrowSummary {
+   pub seller: ActorId,
+   pub buyer: ActorId,
+   pub arbiter: Option<ActorId>,
+   pub tamagotchi_id: Option<ActorId>,
+   pub price: u128,
+   pub deposited: u128,
+   pub currency: Currency,
+   pub state: EscrowState,
+   pub delivery_deadline: Option<u32>,
+   pub release_block: Option<u32>,
+   pub funding_deadline: Option<u32>,
+   pub next_release: Option<u32>,
+   pub periods_paid: u32,
+   pub fee: u128,
+   pub net_to_seller: u128,
+   pub owed_to_seller: u128,
+}
+
+/// Actions the account can perform at the given block.
+#[derive(Debug, Default, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct Permissions {
+   pub deposit: bool,
+   pub confirm_delivery: bool,
+   pub refund: bool,
+   pub cancel: bool,
+   pub open_dispute: bool,
+   pub resolve: bool,
+   pub mark_shipped: bool,
+   pub fund_milestone: bool,
+   pub confirm_milestone: bool,
+   pub withdraw: bool,
+   pub cancel_subscription: bool,
+}
+
+pub const MAX_FEE_BPS: u16 = 10_000;
+/// Every release is scheduled on deposit, so the number of periods
+/// is limited by the gas the deposit can spend.
+pub const MAX_PERIODS: u32 = 24;
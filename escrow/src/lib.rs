@@ -1,83 +1,1068 @@
 #![no_std]
 use codec::{Decode, Encode};
 use scale_info::TypeInfo;
-use gstd::{msg, ActorId, prelude::*};
+use gstd::{exec, msg, ActorId, prelude::*};
 use escrow_io::*;
+use factory_io::FactoryAction;
+use ft_main_io::{FTokenAction, FTokenEvent, LogicAction};
+use hello_world_io::{TmgAction, TmgEvent};
+
+const GAS_FOR_AUTO_RELEASE: u64 = 1_000_000_000;
+// token payouts take several hops through the sharded fungible token
+const GAS_FOR_FT_AUTO_RELEASE: u64 = 10_000_000_000;
+const GAS_FOR_PERIOD_RELEASE: u64 = 1_000_000_000;
 
 #[derive(Default, Encode, Decode, TypeInfo)]
 pub struct Escrow {
    pub seller: ActorId,
    pub buyer: ActorId,
    pub price: u128,
+   pub terms_hash: [u8; 32],
+   pub deposited: u128,
    pub state: EscrowState,
+   pub tamagotchi_id: Option<ActorId>,
+   pub delivery_period: u32,
+   pub delivery_deadline: Option<u32>,
+   pub tracking_hash: Option<[u8; 32]>,
+   pub shipped_at: Option<u32>,
+   pub arbiter: Option<ActorId>,
+   pub arbiter_fee_bps: u16,
+   pub auto_release_period: Option<u32>,
+   pub release_block: Option<u32>,
+   pub milestones: Vec<(Milestone, MilestoneStatus)>,
+   pub currency: Currency,
+   pub transaction_id: TransactionId,
+   pub transactions: BTreeMap<TransactionId, FtTransfer>,
+   pub platform_fee: Option<PlatformFee>,
+   pub fee: u128,
+   pub net_to_seller: u128,
+   pub owed: BTreeMap<ActorId, u128>,
+   pub crowdfunding: Option<Crowdfunding>,
+   pub funding_deadline: Option<u32>,
+   pub contributions: BTreeMap<ActorId, u128>,
+   pub confirmations: BTreeSet<ActorId>,
+   pub subscription: Option<Subscription>,
+   pub periods_paid: u32,
+   pub next_release: Option<u32>,
+   pub factory: Option<ActorId>,
 }
 
 impl Escrow {
-    fn deposit(&mut self) {
+    async fn deposit(&mut self, source: ActorId, terms_hash: [u8; 32]) {
         assert_eq!(
             self.state,
             EscrowState::AwaitingPayment,
             "State must be `AwaitingPayment"
         );
         assert_eq!(
-            msg::source(),
+            terms_hash, self.terms_hash,
+            "The buyer must agree to the terms of the deal"
+        );
+        if self.crowdfunding.is_some() {
+            self.contribute(source);
+            return;
+        }
+        assert_eq!(
+            source,
             self.buyer,
             "The message sender must be a buyer"
         );
-        assert_eq!(
-            msg::value(),
-            self.price,
-            "The attached value must be equal to set price"
+
+        // native deposits accumulate until the price is reached
+        let remaining = self.price - self.deposited;
+        if self.currency == Currency::Native {
+            let value = msg::value();
+            if value < remaining {
+                self.deposited += value;
+                msg::reply(
+                    EscrowEvent::FundsPartiallyDeposited {
+                        remaining: remaining - value,
+                    },
+                    0,
+                )
+                .expect("Error in reply `EscrowEvent::FundsPartiallyDeposited`");
+                return;
+            }
+
+            if value > remaining {
+                msg::send(self.buyer, EscrowEvent::ChangeReturned(value - remaining), value - remaining)
+                    .expect("Unable to return the change to the buyer");
+            }
+        }
+
+        // the escrow doesn't change until the tokens arrive
+        if let Currency::FungibleToken(_) = self.currency {
+            if !self.receive(remaining, TransferKind::Deposit).await {
+                msg::reply(EscrowEvent::DepositFailed, 0)
+                    .expect("Error in reply `EscrowEvent::DepositFailed`");
+                return;
+            }
+        }
+
+        let event = self.complete_deposit().await;
+        msg::reply(event, 0).expect("Error in reply `EscrowEvent`");
+    }
+
+    // the price is in the escrow, so the deal goes on
+    async fn complete_deposit(&mut self) -> EscrowEvent {
+        self.deposited = self.price;
+        self.state = if self.tamagotchi_id.is_some() {
+            EscrowState::Delivering
+        } else if self.subscription.is_some() {
+            EscrowState::Subscribed
+        } else {
+            EscrowState::AwaitingDelivery
+        };
+        self.delivery_deadline = Some(exec::block_height().saturating_add(self.delivery_period));
+        for (_, status) in self.milestones.iter_mut() {
+            *status = MilestoneStatus::Funded;
+        }
+
+        if let Some(tamagotchi_id) = self.tamagotchi_id {
+            return self.deliver_tamagotchi(&tamagotchi_id).await;
+        }
+
+        if self.subscription.is_some() {
+            self.schedule_releases();
+            return EscrowEvent::SubscriptionStarted;
+        }
+
+        EscrowEvent::FundsDeposited
+    }
+
+    fn contribute(&mut self, contributor: ActorId) {
+        let deadline = self.funding_deadline.expect("Funding deadline is not set");
+        assert!(
+            exec::block_height() < deadline,
+            "The funding deadline has passed"
         );
 
+        let contributed = self.contributions.get(&contributor).copied().unwrap_or_default();
+        let remaining = match &self.crowdfunding {
+            Some(Crowdfunding {
+                contributors: Contributors::Shares(shares),
+                ..
+            }) => {
+                let share = shares
+                    .iter()
+                    .find(|(buyer, _)| *buyer == contributor)
+                    .map(|(_, share)| *share)
+                    .expect("The message sender must be a buyer");
+                share - contributed
+            }
+            _ => self.price - self.deposited,
+        };
+        let value = msg::value();
+        assert!(value != 0, "The contribution must be non-zero");
+        assert!(remaining != 0, "The share is already contributed");
+
+        let accepted = value.min(remaining);
+        if value > accepted {
+            msg::send(contributor, EscrowEvent::ChangeReturned(value - accepted), value - accepted)
+                .expect("Unable to return the change to the contributor");
+        }
+        *self.contributions.entry(contributor).or_default() += accepted;
+        self.deposited += accepted;
+
+        if self.deposited < self.price {
+            msg::reply(
+                EscrowEvent::FundsPartiallyDeposited {
+                    remaining: self.price - self.deposited,
+                },
+                0,
+            )
+            .expect("Error in reply `EscrowEvent::FundsPartiallyDeposited`");
+            return;
+        }
+
         self.state = EscrowState::AwaitingDelivery;
+        self.delivery_deadline = Some(exec::block_height().saturating_add(self.delivery_period));
+
         msg::reply(EscrowEvent::FundsDeposited, 0)
             .expect("Error in reply `EscrowEvent::FundsDeposited`");
     }
 
-    fn confirm_delivery(&mut self) {
-        assert_eq!(
-            msg::source(),
-            self.buyer,
+    async fn deliver_tamagotchi(&mut self, tamagotchi_id: &ActorId) -> EscrowEvent {
+        // checking the seller still owns the tamagotchi
+        let owner = msg::send_for_reply_as::<_, TmgEvent>(
+            *tamagotchi_id,
+            TmgAction::Owner,
+            0,
+        )
+        .expect("Error in sending a message `TmgAction::Owner`")
+        .await;
+
+        let delivered = match owner {
+            Ok(TmgEvent::Owner(owner)) if owner == self.seller => {
+                // the escrow transfers the tamagotchi as an approved account
+                let result = msg::send_for_reply_as::<_, TmgEvent>(
+                    *tamagotchi_id,
+                    TmgAction::Transfer(self.buyer),
+                    0,
+                )
+                .expect("Error in sending a message `TmgAction::Transfer`")
+                .await;
+                matches!(result, Ok(TmgEvent::Transfer(_)))
+            }
+            _ => false,
+        };
+
+        if !delivered {
+            self.deposited = 0;
+            self.state = EscrowState::AwaitingPayment;
+            self.delivery_deadline = None;
+            self.pay(self.buyer, self.price, EscrowEvent::TamagotchiNotDelivered(*tamagotchi_id)).await;
+            return EscrowEvent::TamagotchiNotDelivered(*tamagotchi_id);
+        }
+
+        self.state = EscrowState::Closed;
+        self.pay_seller(self.price).await;
+        self.close();
+
+        EscrowEvent::TamagotchiDelivered(*tamagotchi_id)
+    }
+
+    async fn confirm_delivery(&mut self, source: ActorId) {
+        assert!(
+            self.is_awaiting_delivery(),
+            "State must be `AwaitingDelivery` or `Shipped`"
+        );
+
+        // the funds are released once enough contributors have confirmed
+        if let Some(crowdfunding) = &self.crowdfunding {
+            assert!(
+                self.contributions.contains_key(&source),
+                "The message sender must be a contributor"
+            );
+            self.confirmations.insert(source);
+
+            let quorum = crowdfunding.quorum.min(self.contributions.len() as u32);
+            let confirmations = self.confirmations.len() as u32;
+            if confirmations < quorum {
+                msg::reply(EscrowEvent::ConfirmationRecorded { confirmations, quorum }, 0)
+                    .expect("Failed to reply `EscrowEvent::ConfirmationRecorded`");
+                return;
+            }
+        } else {
+            assert_eq!(source, self.buyer, "The message sender must be a buyer");
+        }
+        assert!(
+            self.tamagotchi_id.is_none(),
+            "The tamagotchi is delivered by the escrow itself"
+        );
+        assert!(
+            self.milestones.iter().all(|(_, status)| *status != MilestoneStatus::Pending),
+            "All milestones must be funded"
+        );
+
+        let amount = self.locked_amount();
+        self.settle_milestones(MilestoneStatus::Released);
+        self.state = EscrowState::Closed;
+        let (fee, net_to_seller) = self.pay_seller(amount).await;
+        self.close();
+
+        msg::reply(EscrowEvent::DeliveryConfirmed { fee, net_to_seller }, 0)
+            .expect("Failed to reply `EscrowEvent::DeliveryConfirmed`");
+    }
+
+    async fn refund(&mut self, source: ActorId) {
+        assert!(
+            self.is_buyer(&source),
             "The message sender must be a buyer"
         );
+        if self.crowdfunding.is_some() && self.state == EscrowState::AwaitingPayment {
+            self.refund_contributions();
+            return;
+        }
+        // the shipment is only the seller's word, so it doesn't extend the deadline
+        assert!(
+            self.is_awaiting_delivery(),
+            "State must be `AwaitingDelivery` or `Shipped`"
+        );
+        let deadline = self.delivery_deadline.expect("Delivery deadline is not set");
+        assert!(
+            exec::block_height() >= deadline,
+            "The delivery deadline has not passed yet"
+        );
+
+        let amount = self.locked_amount();
+        self.settle_milestones(MilestoneStatus::Refunded);
+        self.state = EscrowState::Refunded;
+        self.pay_buyers(amount, EscrowEvent::Refunded).await;
+
+        msg::reply(EscrowEvent::Refunded, 0)
+            .expect("Failed to reply `EscrowEvent::Refunded`");
+    }
+
+    // the price wasn't collected in time, so every contributor takes their part back
+    fn refund_contributions(&mut self) {
+        let deadline = self.funding_deadline.expect("Funding deadline is not set");
+        assert!(
+            exec::block_height() >= deadline,
+            "The funding deadline has not passed yet"
+        );
+
+        self.state = EscrowState::Refunded;
+        self.pay_contributors(self.deposited, &EscrowEvent::Refunded);
+
+        msg::reply(EscrowEvent::Refunded, 0)
+            .expect("Failed to reply `EscrowEvent::Refunded`");
+    }
+
+    fn cancel(&mut self, source: ActorId) {
+        assert!(
+            source == self.buyer || source == self.seller,
+            "The message sender must be a buyer or a seller"
+        );
+        assert_eq!(
+            self.state,
+            EscrowState::AwaitingPayment,
+            "State must be `AwaitingPayment"
+        );
+
+        // returning the partial deposit
+        if self.crowdfunding.is_some() {
+            self.pay_contributors(self.deposited, &EscrowEvent::Cancelled);
+        } else if self.deposited != 0 {
+            msg::send(self.buyer, EscrowEvent::Cancelled, self.deposited)
+                .expect("Unable to refund the buyer");
+        }
+        self.deposited = 0;
+        self.state = EscrowState::Cancelled;
+
+        msg::reply(EscrowEvent::Cancelled, 0)
+            .expect("Failed to reply `EscrowEvent::Cancelled`");
+    }
+
+    fn open_dispute(&mut self, source: ActorId) {
+        assert!(
+            self.is_buyer(&source) || source == self.seller,
+            "The message sender must be a buyer or a seller"
+        );
+        assert!(self.arbiter.is_some(), "The escrow has no arbiter");
+        assert!(
+            self.is_awaiting_delivery(),
+            "State must be `AwaitingDelivery` or `Shipped`"
+        );
+
+        self.state = EscrowState::Disputed;
+
+        msg::reply(EscrowEvent::DisputeOpened(source), 0)
+            .expect("Failed to reply `EscrowEvent::DisputeOpened`");
+    }
+
+    async fn resolve(&mut self, source: ActorId, to_seller: u128, to_buyer: u128) {
+        let arbiter = self.arbiter.expect("The escrow has no arbiter");
+        assert_eq!(
+            source,
+            arbiter,
+            "The message sender must be an arbiter"
+        );
+        assert_eq!(
+            self.state,
+            EscrowState::Disputed,
+            "State must be `Disputed"
+        );
+        assert_eq!(
+            to_seller.checked_add(to_buyer),
+            Some(self.locked_amount()),
+            "The split must sum to the locked funds"
+        );
+
+        // the arbiter fee is taken from both shares proportionally
+        let seller_fee = fee_of(to_seller, self.arbiter_fee_bps);
+        let buyer_fee = fee_of(to_buyer, self.arbiter_fee_bps);
+        let fee = seller_fee + buyer_fee;
+
+        // the funded milestones are paid out, mostly to the buyer or to the seller
+        self.settle_milestones(if to_buyer > to_seller {
+            MilestoneStatus::Refunded
+        } else {
+            MilestoneStatus::Released
+        });
+        self.state = EscrowState::Resolved;
+        let (platform_fee, net_to_seller) = self.pay_seller(to_seller - seller_fee).await;
+        self.pay_buyers(to_buyer - buyer_fee, "FUNDS").await;
+        self.pay(arbiter, fee, "FUNDS").await;
+
+        msg::reply(
+            EscrowEvent::Resolved {
+                to_seller: net_to_seller,
+                to_buyer: to_buyer - buyer_fee,
+                fee,
+                platform_fee,
+            },
+            0,
+        )
+        .expect("Failed to reply `EscrowEvent::Resolved`");
+    }
+
+    fn mark_shipped(&mut self, source: ActorId, tracking_hash: [u8; 32]) {
+        assert_eq!(
+            source,
+            self.seller,
+            "The message sender must be a seller"
+        );
         assert_eq!(
             self.state,
             EscrowState::AwaitingDelivery,
             "State must be `AwaitingDelivery"
         );
+        assert!(
+            self.tamagotchi_id.is_none(),
+            "The tamagotchi is delivered by the escrow itself"
+        );
+
+        self.state = EscrowState::Shipped;
+        self.tracking_hash = Some(tracking_hash);
+        self.shipped_at = Some(exec::block_height());
+
+        // giving the buyer a chance to react before releasing the funds
+        if let Some(period) = self.auto_release_period {
+            self.release_block = Some(exec::block_height().saturating_add(period));
+            msg::send_with_gas_delayed(
+                exec::program_id(),
+                EscrowAction::AutoRelease,
+                self.gas_for_auto_release(),
+                0,
+                period,
+            )
+            .expect("Error while sending delayed.");
+        }
 
-        msg::send(self.seller, "FUNDS", self.price).expect("Unable to send funds to the seller");
+        msg::reply(EscrowEvent::Shipped, 0)
+            .expect("Failed to reply `EscrowEvent::Shipped`");
+    }
+
+    async fn auto_release(&mut self) {
+        assert_eq!(
+            msg::source(),
+            exec::program_id(),
+            "Only the escrow itself can release the funds automatically"
+        );
+
+        // the buyer has already confirmed the delivery or opened a dispute
+        if self.state != EscrowState::Shipped {
+            return;
+        }
+        match self.release_block {
+            Some(block) if exec::block_height() >= block => {}
+            _ => return,
+        }
+
+        let amount = self.locked_amount();
+        self.settle_milestones(MilestoneStatus::Released);
         self.state = EscrowState::Closed;
+        self.pay_seller(amount).await;
+        self.close();
 
-        msg::reply(EscrowEvent::DeliveryConfirmed, 0)
-            .expect("Failed to reply `EscrowEvent::DeliveryConfirmed`");
+        msg::send(self.seller, EscrowEvent::AutoReleased, 0)
+            .expect("Unable to notify the seller");
+    }
+
+    async fn release_period(&mut self) {
+        assert_eq!(
+            msg::source(),
+            exec::program_id(),
+            "Only the escrow itself can release the periods"
+        );
+
+        // the subscription has been cancelled
+        if self.state != EscrowState::Subscribed {
+            return;
+        }
+        let subscription = self.subscription.expect("The escrow has no subscription");
+
+        self.periods_paid += 1;
+        let period = self.periods_paid;
+        if period == subscription.periods {
+            self.next_release = None;
+            self.state = EscrowState::Closed;
+        } else {
+            self.next_release = Some(exec::block_height().saturating_add(subscription.period));
+        }
+        self.pay_seller(self.price / subscription.periods as u128).await;
+        if period == subscription.periods {
+            self.close();
+        }
+
+        msg::send(self.seller, EscrowEvent::PeriodReleased(period), 0)
+            .expect("Unable to notify the seller");
+    }
+
+    async fn cancel_subscription(&mut self, source: ActorId) {
+        assert_eq!(
+            source,
+            self.buyer,
+            "The message sender must be a buyer"
+        );
+        assert_eq!(
+            self.state,
+            EscrowState::Subscribed,
+            "State must be `Subscribed"
+        );
+        let subscription = self.subscription.expect("The escrow has no subscription");
+
+        // the delayed release is ignored once the state is changed
+        let refunded = self.price / subscription.periods as u128
+            * (subscription.periods - self.periods_paid) as u128;
+        self.state = EscrowState::Cancelled;
+        self.next_release = None;
+        self.pay(self.buyer, refunded, EscrowEvent::SubscriptionCancelled { refunded }).await;
+
+        msg::reply(EscrowEvent::SubscriptionCancelled { refunded }, 0)
+            .expect("Failed to reply `EscrowEvent::SubscriptionCancelled`");
+    }
+
+    async fn fund_milestone(&mut self, source: ActorId, milestone_id: MilestoneId) {
+        assert_eq!(
+            source,
+            self.buyer,
+            "The message sender must be a buyer"
+        );
+        assert!(
+            self.state == EscrowState::AwaitingPayment || self.is_awaiting_delivery(),
+            "State must be `AwaitingPayment`, `AwaitingDelivery` or `Shipped`"
+        );
+        assert!(
+            self.deposited == 0 || self.state != EscrowState::AwaitingPayment,
+            "The whole price is being deposited"
+        );
+
+        let (milestone, status) = self
+            .milestones
+            .get(milestone_id as usize)
+            .expect("The milestone doesn't exist");
+        assert_eq!(
+            *status,
+            MilestoneStatus::Pending,
+            "The milestone is already funded"
+        );
+        let amount = milestone.amount;
+
+        if !self.receive(amount, TransferKind::FundMilestone(milestone_id)).await {
+            msg::reply(EscrowEvent::DepositFailed, 0)
+                .expect("Error in reply `EscrowEvent::DepositFailed`");
+            return;
+        }
+
+        let event = self.complete_milestone_funding(milestone_id).await;
+        msg::reply(event, 0).expect("Failed to reply `EscrowEvent`");
+    }
+
+    async fn complete_milestone_funding(&mut self, milestone_id: MilestoneId) -> EscrowEvent {
+        // the funds were released automatically while the tokens were on the way
+        if self.state != EscrowState::AwaitingPayment && !self.is_awaiting_delivery() {
+            let amount = self.milestones[milestone_id as usize].0.amount;
+            self.pay(self.buyer, amount, EscrowEvent::Refunded).await;
+            return EscrowEvent::Refunded;
+        }
+
+        self.milestones[milestone_id as usize].1 = MilestoneStatus::Funded;
+        if self.state == EscrowState::AwaitingPayment {
+            self.state = EscrowState::AwaitingDelivery;
+            self.delivery_deadline = Some(exec::block_height().saturating_add(self.delivery_period));
+        }
+
+        EscrowEvent::MilestoneFunded(milestone_id)
+    }
+
+    async fn confirm_milestone(&mut self, source: ActorId, milestone_id: MilestoneId) {
+        assert_eq!(
+            source,
+            self.buyer,
+            "The message sender must be a buyer"
+        );
+        assert!(
+            self.is_awaiting_delivery(),
+            "State must be `AwaitingDelivery` or `Shipped`"
+        );
+
+        let (milestone, status) = self
+            .milestones
+            .get_mut(milestone_id as usize)
+            .expect("The milestone doesn't exist");
+        assert_eq!(
+            *status,
+            MilestoneStatus::Funded,
+            "The milestone must be funded"
+        );
+        *status = MilestoneStatus::Released;
+        let amount = milestone.amount;
+
+        let last_milestone = self
+            .milestones
+            .iter()
+            .all(|(_, status)| *status == MilestoneStatus::Released);
+        if last_milestone {
+            self.state = EscrowState::Closed;
+        }
+        self.pay_seller(amount).await;
+        if last_milestone {
+            self.close();
+        }
+
+        msg::reply(EscrowEvent::MilestoneReleased(milestone_id), 0)
+            .expect("Failed to reply `EscrowEvent::MilestoneReleased`");
+    }
+
+    fn withdraw(&mut self, source: ActorId) {
+        let amount = self.owed.remove(&source).expect("Nothing to withdraw");
+
+        if self.state == EscrowState::AwaitingWithdrawal {
+            self.close();
+        }
+
+        // the factory doesn't forward the value, so it's sent to the account directly
+        if source != msg::source() {
+            msg::send(source, EscrowEvent::Withdrawn(amount), amount)
+                .expect("Unable to send the funds");
+            msg::reply(EscrowEvent::Withdrawn(amount), 0)
+                .expect("Failed to reply `EscrowEvent::Withdrawn`");
+            return;
+        }
+
+        msg::reply(EscrowEvent::Withdrawn(amount), amount)
+            .expect("Failed to reply `EscrowEvent::Withdrawn`");
+    }
+
+    async fn continue_transaction(&mut self, transaction_id: TransactionId) {
+        let kind = self
+            .transactions
+            .get(&transaction_id)
+            .expect("The transaction doesn't exist")
+            .kind;
+
+        if !self.ft_transfer(transaction_id).await {
+            msg::reply(EscrowEvent::TransferFailed(transaction_id), 0)
+                .expect("Failed to reply `EscrowEvent::TransferFailed`");
+            return;
+        }
+
+        // the deposit goes on the same way as if the transfer hadn't been interrupted
+        match kind {
+            TransferKind::Deposit => {
+                self.complete_deposit().await;
+            }
+            TransferKind::FundMilestone(milestone_id) => {
+                self.complete_milestone_funding(milestone_id).await;
+            }
+            TransferKind::Payout => {
+                if self.state == EscrowState::AwaitingWithdrawal {
+                    self.close();
+                }
+            }
+        }
+        msg::reply(EscrowEvent::TransferCompleted(transaction_id), 0)
+            .expect("Failed to reply `EscrowEvent::TransferCompleted`");
+    }
+
+    // taking the funds from the buyer
+    async fn receive(&mut self, amount: u128, kind: TransferKind) -> bool {
+        match self.currency {
+            Currency::Native => {
+                assert_eq!(
+                    msg::value(),
+                    amount,
+                    "The attached value must be equal to the amount due"
+                );
+                true
+            }
+            Currency::FungibleToken(_) => {
+                assert_eq!(msg::value(), 0, "The escrow accepts fungible tokens only");
+                let transaction_id = self.new_transfer(self.buyer, exec::program_id(), amount, kind);
+                self.ft_transfer(transaction_id).await
+            }
+        }
+    }
+
+    // the payload comes along with the native value only
+    async fn pay<E: Encode>(&mut self, to: ActorId, amount: u128, payload: E) {
+        if amount == 0 {
+            return;
+        }
+
+        match self.currency {
+            Currency::Native => {
+                msg::send(to, payload, amount).expect("Unable to send funds");
+            }
+            Currency::FungibleToken(_) => {
+                let transaction_id = self.new_transfer(exec::program_id(), to, amount, TransferKind::Payout);
+                self.ft_transfer(transaction_id).await;
+            }
+        }
+    }
+
+    // the platform fee is deducted from every payout to the seller,
+    // native proceeds are kept until the seller withdraws them
+    async fn pay_seller(&mut self, amount: u128) -> (u128, u128) {
+        let (recipient, fee) = match self.platform_fee {
+            Some(PlatformFee { recipient, fee_bps }) => (recipient, fee_of(amount, fee_bps)),
+            None => (ActorId::zero(), 0),
+        };
+        let net_to_seller = amount - fee;
+        self.fee += fee;
+        self.net_to_seller += net_to_seller;
+
+        self.pay(recipient, fee, "FEE").await;
+        match self.currency {
+            Currency::Native if net_to_seller != 0 => {
+                *self.owed.entry(self.seller).or_default() += net_to_seller;
+            }
+            _ => self.pay(self.seller, net_to_seller, "FUNDS").await,
+        }
+
+        (fee, net_to_seller)
+    }
+
+    async fn pay_buyers<E: Encode>(&mut self, amount: u128, payload: E) {
+        if self.crowdfunding.is_some() {
+            self.pay_contributors(amount, &payload);
+        } else {
+            self.pay(self.buyer, amount, payload).await;
+        }
+    }
+
+    // the amount is split in proportion to the contributions,
+    // the last contributor takes the rounding remainder
+    fn pay_contributors<E: Encode>(&self, amount: u128, payload: &E) {
+        let mut left = amount;
+        let mut contributions = self.contributions.iter().peekable();
+        while let Some((contributor, contribution)) = contributions.next() {
+            let share = if contributions.peek().is_none() {
+                left
+            } else {
+                amount * contribution / self.deposited
+            };
+            left -= share;
+            if share != 0 {
+                msg::send(*contributor, payload, share)
+                    .expect("Unable to pay the contributor");
+            }
+        }
+    }
+
+    // the deal is closed once the seller has got the proceeds:
+    // native ones are withdrawn, failed token payouts are continued
+    fn close(&mut self) {
+        let paying_seller = self
+            .transactions
+            .values()
+            .any(|transfer| transfer.kind == TransferKind::Payout && transfer.to == self.seller);
+        self.state = if self.owed.is_empty() && !paying_seller {
+            EscrowState::Closed
+        } else {
+            EscrowState::AwaitingWithdrawal
+        };
+    }
+
+    fn new_transfer(&mut self, from: ActorId, to: ActorId, amount: u128, kind: TransferKind) -> TransactionId {
+        let transaction_id = self.transaction_id;
+        self.transaction_id = self.transaction_id.wrapping_add(1);
+        self.transactions.insert(
+            transaction_id,
+            FtTransfer {
+                from,
+                to,
+                amount,
+                kind,
+            },
+        );
+        transaction_id
+    }
+
+    // the transfer is kept until the reply comes, so an interrupted one
+    // can be resent with the same transaction id
+    async fn ft_transfer(&mut self, transaction_id: TransactionId) -> bool {
+        let ft_contract_id = match self.currency {
+            Currency::FungibleToken(ft_contract_id) => ft_contract_id,
+            Currency::Native => unreachable!("The escrow doesn't use fungible tokens"),
+        };
+        let transfer = self
+            .transactions
+            .get(&transaction_id)
+            .cloned()
+            .expect("The transaction doesn't exist");
+
+        let result = msg::send_for_reply_as::<_, FTokenEvent>(
+            ft_contract_id,
+            FTokenAction::Message {
+                transaction_id,
+                payload: LogicAction::Transfer {
+                    sender: transfer.from,
+                    recipient: transfer.to,
+                    amount: transfer.amount,
+                },
+            },
+            0,
+        )
+        .expect("Error in sending a message `FTokenAction::Message`")
+        .await;
+
+        match result {
+            // the transfer may have been completed by another message already
+            Ok(FTokenEvent::Ok) => self.transactions.remove(&transaction_id).is_some(),
+            // the buyer's tokens are not moved, so the deposit is dropped,
+            // while the payout is retried under a new id since the token
+            // keeps the outcome of every transaction
+            Ok(FTokenEvent::Err) => {
+                self.transactions.remove(&transaction_id);
+                if transfer.kind == TransferKind::Payout {
+                    self.new_transfer(transfer.from, transfer.to, transfer.amount, transfer.kind);
+                }
+                false
+            }
+            // interrupted transfers stay for a retry
+            _ => false,
+        }
+    }
+
+    // funds held by the escrow: the price or the funded milestones
+    fn locked_amount(&self) -> u128 {
+        if self.milestones.is_empty() {
+            return self.price;
+        }
+
+        self.milestones
+            .iter()
+            .filter(|(_, status)| *status == MilestoneStatus::Funded)
+            .map(|(milestone, _)| milestone.amount)
+            .sum()
+    }
+
+    fn settle_milestones(&mut self, new_status: MilestoneStatus) {
+        for (_, status) in self.milestones.iter_mut() {
+            if *status == MilestoneStatus::Funded {
+                *status = new_status;
+            }
+        }
+    }
+
+    // all the releases are paid by the deposit, so a release
+    // doesn't have to spare its own gas for the next one
+    fn schedule_releases(&mut self) {
+        let Subscription { period, periods } = self.subscription.expect("The escrow has no subscription");
+        self.next_release = Some(exec::block_height().saturating_add(period));
+        for release in 1..=periods {
+            msg::send_with_gas_delayed(
+                exec::program_id(),
+                EscrowAction::ReleasePeriod,
+                GAS_FOR_PERIOD_RELEASE,
+                0,
+                period.saturating_mul(release),
+            )
+            .expect("Error while sending delayed.");
+        }
+    }
+
+    // tokens sent by the buyer haven't arrived yet
+    fn is_receiving(&self) -> bool {
+        self.transactions
+            .values()
+            .any(|transfer| transfer.kind != TransferKind::Payout)
+    }
+
+    fn gas_for_auto_release(&self) -> u64 {
+        match self.currency {
+            Currency::Native => GAS_FOR_AUTO_RELEASE,
+            Currency::FungibleToken(_) => GAS_FOR_FT_AUTO_RELEASE,
+        }
+    }
+
+    fn is_buyer(&self, account: &ActorId) -> bool {
+        *account == self.buyer || self.contributions.contains_key(account)
+    }
+
+    fn is_awaiting_delivery(&self) -> bool {
+        self.state == EscrowState::AwaitingDelivery || self.state == EscrowState::Shipped
     }
 }
 
+fn fee_of(amount: u128, fee_bps: u16) -> u128 {
+    amount * fee_bps as u128 / MAX_FEE_BPS as u128
+}
+
 static mut ESCROW: Option<Escrow> = None;
 
-#[no_mangle]
-extern "C" fn handle () {
+#[gstd::async_main]
+async fn main() {
     let action: EscrowAction = msg::load().expect("Unable to decode `EscrowAction`");
 
     let escrow: &mut Escrow = unsafe { ESCROW.as_mut().expect("The contract is not initialized") };
+    let state = escrow.state;
+
+    // only the factory that created the escrow can act on behalf of the parties
+    let (source, action) = match action {
+        EscrowAction::OnBehalf { account, action } => {
+            assert!(
+                escrow.factory == Some(msg::source()),
+                "The message sender must be the factory"
+            );
+            (account, *action)
+        }
+        action => (msg::source(), action),
+    };
+
+    // the deposit is settled before anything else, see `Continue`
+    if escrow.is_receiving() {
+        assert!(
+            matches!(
+                action,
+                EscrowAction::Continue(_)
+                    | EscrowAction::AutoRelease
+                    | EscrowAction::Withdraw
+                    | EscrowAction::State
+            ),
+            "The escrow is waiting for the deposit"
+        );
+    }
 
     match action {
-        EscrowAction::Deposit => escrow.deposit(),
-        EscrowAction::ConfirmDelivery => escrow.confirm_delivery(),
+        EscrowAction::Deposit { terms_hash } => escrow.deposit(source, terms_hash).await,
+        EscrowAction::ConfirmDelivery => escrow.confirm_delivery(source).await,
+        EscrowAction::Refund => escrow.refund(source).await,
+        EscrowAction::Cancel => escrow.cancel(source),
+        EscrowAction::OpenDispute => escrow.open_dispute(source),
+        EscrowAction::Resolve {
+            to_seller,
+            to_buyer,
+        } => escrow.resolve(source, to_seller, to_buyer).await,
+        EscrowAction::MarkShipped { tracking_hash } => escrow.mark_shipped(source, tracking_hash),
+        EscrowAction::AutoRelease => escrow.auto_release().await,
+        EscrowAction::FundMilestone(milestone_id) => escrow.fund_milestone(source, milestone_id).await,
+        EscrowAction::ConfirmMilestone(milestone_id) => escrow.confirm_milestone(source, milestone_id).await,
+        EscrowAction::Continue(transaction_id) => escrow.continue_transaction(transaction_id).await,
+        EscrowAction::Withdraw => escrow.withdraw(source),
+        EscrowAction::ReleasePeriod => escrow.release_period().await,
+        EscrowAction::CancelSubscription => escrow.cancel_subscription(source).await,
+        EscrowAction::State => {
+            msg::reply(EscrowEvent::State(escrow.state), 0)
+                .expect("Failed to reply `EscrowEvent::State`");
+        }
+        EscrowAction::OnBehalf { .. } => panic!("Nested on-behalf actions are not allowed"),
+    }
+
+    // the factory mirrors the state of its escrows
+    if let Some(factory) = escrow.factory {
+        if escrow.state != state {
+            msg::send(factory, FactoryAction::EscrowStateChanged(escrow.state), 0)
+                .expect("Unable to report the state to the factory");
+        }
     }
 }
 
 #[no_mangle]
 extern "C" fn init () {
     let init_config: InitEscrow = msg::load().expect("Error in decoding `InitEscrow`");
+    assert!(
+        init_config.delivery_period != 0,
+        "Delivery period must be non-zero"
+    );
+    assert!(
+        init_config.arbiter_fee_bps <= MAX_FEE_BPS,
+        "Arbiter fee can't exceed 100%"
+    );
+    if let Some(platform_fee) = init_config.platform_fee {
+        assert!(
+            platform_fee.fee_bps <= MAX_FEE_BPS,
+            "Platform fee can't exceed 100%"
+        );
+    }
+    if let Some(crowdfunding) = &init_config.crowdfunding {
+        assert_eq!(
+            init_config.currency,
+            Currency::Native,
+            "Only native deals can be crowdfunded"
+        );
+        assert!(
+            init_config.milestones.is_empty() && init_config.tamagotchi_id.is_none(),
+            "Crowdfunded deals can't have milestones or a tamagotchi"
+        );
+        assert!(crowdfunding.quorum != 0, "Quorum must be non-zero");
+        if let Contributors::Shares(shares) = &crowdfunding.contributors {
+            assert_eq!(
+                shares.iter().map(|(_, share)| share).sum::<u128>(),
+                init_config.price,
+                "Shares must sum to the price"
+            );
+            assert!(
+                crowdfunding.quorum as usize <= shares.len(),
+                "Quorum can't exceed the number of buyers"
+            );
+        }
+    }
+    if let Some(subscription) = init_config.subscription {
+        assert!(
+            subscription.period != 0 && subscription.periods != 0,
+            "Subscription period and number of periods must be non-zero"
+        );
+        assert!(
+            subscription.periods <= MAX_PERIODS,
+            "Too many subscription periods"
+        );
+        assert_eq!(
+            init_config.price % subscription.periods as u128,
+            0,
+            "The price must be divisible by the number of periods"
+        );
+        assert!(
+            init_config.milestones.is_empty()
+                && init_config.tamagotchi_id.is_none()
+                && init_config.crowdfunding.is_none(),
+            "Subscriptions can't have milestones, a tamagotchi or several buyers"
+        );
+    }
+    if !init_config.milestones.is_empty() {
+        assert_eq!(
+            init_config.milestones.iter().map(|milestone| milestone.amount).sum::<u128>(),
+            init_config.price,
+            "Milestone amounts must sum to the price"
+        );
+        assert!(
+            init_config.tamagotchi_id.is_none(),
+            "The tamagotchi can't be sold by milestones"
+        );
+    }
 
     let escrow = Escrow {
         seller: init_config.seller,
         buyer: init_config.buyer,
         price: init_config.price,
+        terms_hash: init_config.terms_hash,
+        deposited: 0,
         state: EscrowState::AwaitingPayment,
+        tamagotchi_id: init_config.tamagotchi_id,
+        delivery_period: init_config.delivery_period,
+        delivery_deadline: None,
+        tracking_hash: None,
+        shipped_at: None,
+        arbiter: init_config.arbiter,
+        arbiter_fee_bps: init_config.arbiter_fee_bps,
+        auto_release_period: init_config.auto_release_period,
+        release_block: None,
+        milestones: init_config
+            .milestones
+            .into_iter()
+            .map(|milestone| (milestone, MilestoneStatus::Pending))
+            .collect(),
+        currency: init_config.currency,
+        transaction_id: 0,
+        transactions: BTreeMap::new(),
+        platform_fee: init_config.platform_fee,
+        fee: 0,
+        net_to_seller: 0,
+        owed: BTreeMap::new(),
+        funding_deadline: init_config
+            .crowdfunding
+            .as_ref()
+            .map(|crowdfunding| exec::block_height().saturating_add(crowdfunding.funding_period)),
+        crowdfunding: init_config.crowdfunding,
+        contributions: BTreeMap::new(),
+        confirmations: BTreeSet::new(),
+        subscription: init_config.subscription,
+        periods_paid: 0,
+        next_release: None,
+        factory: init_config.from_factory.then(msg::source),
     };
     unsafe { ESCROW = Some(escrow) };
 }
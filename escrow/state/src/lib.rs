@@ -7,6 +7,27 @@ use escrow_io::*;
 pub mod metafns {
     pub type State = Escrow;
 
+    pub fn summary(state: State) -> EscrowSummary {
+        EscrowSummary {
+            seller: state.seller,
+            buyer: state.buyer,
+            arbiter: state.arbiter,
+            tamagotchi_id: state.tamagotchi_id,
+            price: state.price,
+            deposited: state.deposited,
+            currency: state.currency,
+            delivery_deadline: state.delivery_deadline,
+            release_block: state.release_block,
+            funding_deadline: state.funding_deadline,
+            next_release: state.next_release,
+            periods_paid: state.periods_paid,
+            fee: state.fee,
+            net_to_seller: state.net_to_seller,
+            owed_to_seller: state.owed.get(&state.seller).copied().unwrap_or_default(),
+            state: state.state,
+        }
+    }
+
     pub fn seller(state: State) -> ActorId {
         state.seller
     }
@@ -15,7 +36,227 @@ pub mod metafns {
         state.buyer
     }
 
+    pub fn price(state: State) -> u128 {
+        state.price
+    }
+
+    pub fn deposited(state: State) -> u128 {
+        state.deposited
+    }
+
+    pub fn remaining_deposit(state: State) -> u128 {
+        state.price.saturating_sub(state.deposited)
+    }
+
     pub fn escrow_state(state: State) -> EscrowState {
         state.state
     }
+
+    pub fn delivery_deadline(state: State) -> Option<u32> {
+        state.delivery_deadline
+    }
+
+    pub fn blocks_to_delivery_deadline(state: State, block: u32) -> Option<u32> {
+        state.delivery_deadline.map(|deadline| deadline.saturating_sub(block))
+    }
+
+    pub fn terms_hash(state: State) -> [u8; 32] {
+        state.terms_hash
+    }
+
+    pub fn tracking_hash(state: State) -> Option<[u8; 32]> {
+        state.tracking_hash
+    }
+
+    pub fn shipped_at(state: State) -> Option<u32> {
+        state.shipped_at
+    }
+
+    pub fn is_refundable(state: State, block: u32) -> bool {
+        matches!(state.state, EscrowState::AwaitingDelivery | EscrowState::Shipped)
+            && state.delivery_deadline.map_or(false, |deadline| block >= deadline)
+    }
+
+    pub fn permissions(state: State, query: (ActorId, u32)) -> Permissions {
+        let (account, block) = query;
+        permissions_of(&state, &account, block)
+    }
+
+    pub fn is_cancellable(state: State) -> bool {
+        state.state == EscrowState::AwaitingPayment
+    }
+
+    pub fn arbiter(state: State) -> Option<ActorId> {
+        state.arbiter
+    }
+
+    pub fn is_disputed(state: State) -> bool {
+        state.state == EscrowState::Disputed
+    }
+
+    pub fn is_resolved(state: State) -> bool {
+        state.state == EscrowState::Resolved
+    }
+
+    pub fn release_block(state: State) -> Option<u32> {
+        state.release_block
+    }
+
+    pub fn blocks_to_release(state: State, block: u32) -> Option<u32> {
+        state.release_block.map(|release| release.saturating_sub(block))
+    }
+
+    pub fn milestones(state: State) -> Vec<(Milestone, MilestoneStatus)> {
+        state.milestones
+    }
+
+    pub fn milestone_status(state: State, milestone_id: MilestoneId) -> Option<MilestoneStatus> {
+        state
+            .milestones
+            .get(milestone_id as usize)
+            .map(|(_, status)| *status)
+    }
+
+    pub fn released_amount(state: State) -> u128 {
+        state
+            .milestones
+            .iter()
+            .filter(|(_, status)| *status == MilestoneStatus::Released)
+            .map(|(milestone, _)| milestone.amount)
+            .sum()
+    }
+
+    pub fn platform_fee(state: State) -> Option<PlatformFee> {
+        state.platform_fee
+    }
+
+    pub fn fee(state: State) -> u128 {
+        state.fee
+    }
+
+    pub fn net_to_seller(state: State) -> u128 {
+        state.net_to_seller
+    }
+
+    pub fn owed(state: State, account: ActorId) -> u128 {
+        state.owed.get(&account).copied().unwrap_or_default()
+    }
+
+    pub fn funding_deadline(state: State) -> Option<u32> {
+        state.funding_deadline
+    }
+
+    pub fn blocks_to_funding_deadline(state: State, block: u32) -> Option<u32> {
+        state.funding_deadline.map(|deadline| deadline.saturating_sub(block))
+    }
+
+    pub fn contributions(state: State) -> Vec<(ActorId, u128)> {
+        state.contributions.into_iter().collect()
+    }
+
+    pub fn confirmations(state: State) -> u32 {
+        state.confirmations.len() as u32
+    }
+
+    pub fn periods_paid(state: State) -> u32 {
+        state.periods_paid
+    }
+
+    pub fn periods_remaining(state: State) -> u32 {
+        match (state.subscription, state.state) {
+            (Some(_), EscrowState::Cancelled) | (None, _) => 0,
+            (Some(subscription), _) => subscription.periods - state.periods_paid,
+        }
+    }
+
+    pub fn next_release(state: State) -> Option<u32> {
+        state.next_release
+    }
+
+    pub fn blocks_to_next_release(state: State, block: u32) -> Option<u32> {
+        state.next_release.map(|release| release.saturating_sub(block))
+    }
+
+    pub fn currency(state: State) -> Currency {
+        state.currency
+    }
+
+    pub fn pending_transactions(state: State) -> Vec<(TransactionId, FtTransfer)> {
+        state.transactions.into_iter().collect()
+    }
+}
+
+// mirrors the checks made by the escrow program
+fn permissions_of(state: &Escrow, account: &ActorId, block: u32) -> Permissions {
+    let is_buyer = *account == state.buyer || state.contributions.contains_key(account);
+    let is_seller = *account == state.seller;
+    let awaiting_delivery = matches!(state.state, EscrowState::AwaitingDelivery | EscrowState::Shipped);
+    let has_milestone = |status: MilestoneStatus| state.milestones.iter().any(|(_, s)| *s == status);
+    let passed = |deadline: Option<u32>| deadline.map_or(false, |deadline| block >= deadline);
+
+    // only the withdrawal is allowed until the deposit is settled
+    let receiving = state
+        .transactions
+        .values()
+        .any(|transfer| transfer.kind != TransferKind::Payout);
+    let withdraw = state.owed.get(account).map_or(false, |amount| *amount != 0);
+    if receiving {
+        return Permissions {
+            withdraw,
+            ..Default::default()
+        };
+    }
+
+    let deposit = state.state == EscrowState::AwaitingPayment
+        && match &state.crowdfunding {
+            Some(crowdfunding) => {
+                let contributed = state.contributions.get(account).copied().unwrap_or_default();
+                !passed(state.funding_deadline)
+                    && match &crowdfunding.contributors {
+                        Contributors::Shares(shares) => shares
+                            .iter()
+                            .any(|(buyer, share)| buyer == account && *share > contributed),
+                        Contributors::Open => true,
+                    }
+            }
+            None => *account == state.buyer,
+        };
+    let confirm_delivery = awaiting_delivery
+        && state.tamagotchi_id.is_none()
+        && !has_milestone(MilestoneStatus::Pending)
+        && match state.crowdfunding {
+            Some(_) => {
+                state.contributions.contains_key(account) && !state.confirmations.contains(account)
+            }
+            None => *account == state.buyer,
+        };
+    let refund = is_buyer
+        && match state.state {
+            EscrowState::AwaitingPayment => {
+                state.crowdfunding.is_some() && passed(state.funding_deadline)
+            }
+            EscrowState::AwaitingDelivery | EscrowState::Shipped => passed(state.delivery_deadline),
+            _ => false,
+        };
+
+    Permissions {
+        deposit,
+        confirm_delivery,
+        refund,
+        cancel: (*account == state.buyer || is_seller) && state.state == EscrowState::AwaitingPayment,
+        open_dispute: (is_buyer || is_seller) && state.arbiter.is_some() && awaiting_delivery,
+        resolve: state.arbiter == Some(*account) && state.state == EscrowState::Disputed,
+        mark_shipped: is_seller
+            && state.state == EscrowState::AwaitingDelivery
+            && state.tamagotchi_id.is_none(),
+        fund_milestone: *account == state.buyer
+            && has_milestone(MilestoneStatus::Pending)
+            && (awaiting_delivery
+                || (state.state == EscrowState::AwaitingPayment && state.deposited == 0)),
+        confirm_milestone: *account == state.buyer
+            && awaiting_delivery
+            && has_milestone(MilestoneStatus::Funded),
+        withdraw,
+        cancel_subscription: *account == state.buyer && state.state == EscrowState::Subscribed,
+    }
 }
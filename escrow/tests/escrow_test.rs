@@ -1,24 +1,174 @@
-use escrow_io::{InitEscrow, EscrowAction, EscrowEvent};
-use gtest::{Log, Program, System};
+use escrow_io::{
+    Contributors, Crowdfunding, Currency, Escrow, EscrowAction, EscrowEvent, EscrowState,
+    InitEscrow, Milestone, MilestoneStatus, PlatformFee, Subscription, MAX_PERIODS,
+};
+use ft_main_io::{FTokenAction, FTokenEvent, LogicAction};
+use gstd::ActorId;
+use gtest::{Log, Program, System, WasmProgram};
+use hello_world_io::{Tamagotchi, TmgAction, TmgInit};
+use parity_scale_codec::{Decode, Encode};
+use std::{
+    collections::BTreeMap,
+    sync::{Arc, Mutex},
+};
 const BUYER: u64 = 100;
 const SELLER: u64 = 101;
 const PRICE: u128 = 100_000;
 const ESCROW_ID: u64 = 1;
+const TAMAGOTCHI_ID: u64 = 2;
+const DELIVERY_PERIOD: u32 = 10;
+const ARBITER: u64 = 102;
+const AUTO_RELEASE_PERIOD: u32 = 5;
+const FT_CONTRACT_ID: u64 = 200;
+const PLATFORM: u64 = 300;
+const CO_BUYER: u64 = 103;
+const FACTORY_ID: u64 = 104;
+const FUNDING_PERIOD: u32 = 10;
+const SUBSCRIPTION_PERIOD: u32 = 5;
+const SUBSCRIPTION_PERIODS: u32 = 4;
+const TERMS_HASH: [u8; 32] = [1; 32];
+const TRACKING_HASH: [u8; 32] = [2; 32];
 
-fn init_escrow(sys: &System) {
+fn escrow_config() -> InitEscrow {
+    InitEscrow {
+        seller: SELLER.into(),
+        buyer: BUYER.into(),
+        price: PRICE,
+        terms_hash: TERMS_HASH,
+        tamagotchi_id: None,
+        delivery_period: DELIVERY_PERIOD,
+        arbiter: None,
+        arbiter_fee_bps: 0,
+        auto_release_period: None,
+        milestones: vec![],
+        currency: Currency::Native,
+        platform_fee: None,
+        crowdfunding: None,
+        subscription: None,
+        from_factory: false,
+    }
+}
+
+// sharded fungible token keeping the outcome of every transaction,
+// the first `interrupted` transfers fail without a reply from the token
+// and the first `rejected` ones are answered with `FTokenEvent::Err`
+#[derive(Debug, Clone, Default)]
+struct FtMock {
+    balances: Arc<Mutex<BTreeMap<ActorId, u128>>>,
+    transactions: Arc<Mutex<BTreeMap<u64, bool>>>,
+    interrupted: Arc<Mutex<u32>>,
+    rejected: Arc<Mutex<u32>>,
+}
+
+impl FtMock {
+    fn balance_of(&self, account: u64) -> u128 {
+        let balances = self.balances.lock().unwrap();
+        balances.get(&account.into()).copied().unwrap_or_default()
+    }
+}
+
+impl WasmProgram for FtMock {
+    fn init(&mut self, _payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        Ok(None)
+    }
+
+    fn handle(&mut self, payload: Vec<u8>) -> Result<Option<Vec<u8>>, &'static str> {
+        let FTokenAction::Message {
+            transaction_id,
+            payload: LogicAction::Transfer {
+                sender,
+                recipient,
+                amount,
+            },
+        } = FTokenAction::decode(&mut &payload[..]).map_err(|_| "Unable to decode `FTokenAction`")?
+        else {
+            return Err("Unsupported action");
+        };
+
+        let mut interrupted = self.interrupted.lock().unwrap();
+        if *interrupted != 0 {
+            *interrupted -= 1;
+            return Err("The transfer is interrupted");
+        }
+
+        let mut transactions = self.transactions.lock().unwrap();
+        let ok = *transactions.entry(transaction_id).or_insert_with(|| {
+            let mut rejected = self.rejected.lock().unwrap();
+            if *rejected != 0 {
+                *rejected -= 1;
+                return false;
+            }
+
+            let mut balances = self.balances.lock().unwrap();
+            let balance = balances.entry(sender).or_default();
+            if *balance < amount {
+                return false;
+            }
+            *balance -= amount;
+            *balances.entry(recipient).or_default() += amount;
+            true
+        });
+        let event = if ok { FTokenEvent::Ok } else { FTokenEvent::Err };
+        Ok(Some(event.encode()))
+    }
+
+    fn handle_reply(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn handle_signal(&mut self, _payload: Vec<u8>) -> Result<(), &'static str> {
+        Ok(())
+    }
+
+    fn state(&mut self) -> Result<Vec<u8>, &'static str> {
+        Ok(vec![])
+    }
+}
+
+// the buyer holds the price in tokens
+fn init_ft_escrow_with(sys: &System, config: InitEscrow, interrupted: u32) -> FtMock {
+    init_escrow_with(sys, InitEscrow {
+        currency: Currency::FungibleToken(FT_CONTRACT_ID.into()),
+        ..config
+    });
+
+    let ft = FtMock::default();
+    ft.balances.lock().unwrap().insert(BUYER.into(), PRICE);
+    *ft.interrupted.lock().unwrap() = interrupted;
+    let program = Program::mock_with_id(sys, FT_CONTRACT_ID, ft.clone());
+    let res = program.send_bytes(BUYER, []);
+    assert!(!res.main_failed());
+    ft
+}
+
+fn init_escrow_with(sys: &System, config: InitEscrow) {
     sys.init_logger();
     let escrow = Program::current(&sys);
-    let res = escrow.send(
-        SELLER,
-        InitEscrow {
-            seller: SELLER.into(),
-            buyer: BUYER.into(),
-            price: PRICE,
-        },
-    );
+    let res = escrow.send(SELLER, config);
     assert!(res.log().is_empty());
 }
 
+fn init_escrow(sys: &System) {
+    init_escrow_with(sys, escrow_config());
+}
+
+fn init_tamagotchi_escrow(sys: &System) {
+    init_escrow_with(sys, InitEscrow {
+        tamagotchi_id: Some(TAMAGOTCHI_ID.into()),
+        ..escrow_config()
+    });
+
+    let tamagotchi = Program::from_file(
+        &sys,
+        "../hello-world/target/wasm32-unknown-unknown/debug/hello_world.opt.wasm",
+    );
+    let res = tamagotchi.send(SELLER, TmgInit {
+        name: String::from("Satoshi"),
+        royalty_bps: None,
+    });
+    assert!(!res.main_failed());
+}
+
 #[test]
 fn deposit() {
     let sys = System::new();
@@ -28,7 +178,7 @@ fn deposit() {
 
     sys.mint_to(BUYER, PRICE);
 
-    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit, PRICE);
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
     let log = Log::builder()
         .dest(BUYER)
         .payload(EscrowEvent::FundsDeposited);
@@ -36,6 +186,12 @@ fn deposit() {
 
     let escrow_balance = sys.balance_of(ESCROW_ID);
     assert_eq!(escrow_balance, PRICE);
+
+    let res = escrow.send(SELLER, EscrowAction::State);
+    let log = Log::builder()
+        .dest(SELLER)
+        .payload(EscrowEvent::State(EscrowState::AwaitingDelivery));
+    assert!(res.contains(&log));
 }
 
 #[test]
@@ -46,23 +202,96 @@ fn deposit_failures() {
     let escrow = sys.get_program(ESCROW_ID);
 
     sys.mint_to(BUYER, 2*PRICE);
-    // must fail since BUYER attaches not enough value
-    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit, PRICE - 500);
-    assert!(res.main_failed());
 
     // must fail since the message sender is not BUYER
-    let res = escrow.send(SELLER, EscrowAction::Deposit);
+    let res = escrow.send(SELLER, EscrowAction::Deposit { terms_hash: TERMS_HASH });
+    assert!(res.main_failed());
+
+    // must fail since the buyer agreed to different terms
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: [0; 32] }, PRICE);
     assert!(res.main_failed());
 
     // successful deposit
-    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit, PRICE);
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
     assert!(!res.main_failed());
 
     // must fail since the state must be `AwaitingPayment`
-    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit, PRICE);
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    assert!(res.main_failed());
+}
+
+#[test]
+fn zero_delivery_period() {
+    let sys = System::new();
+    sys.init_logger();
+    let escrow = Program::current(&sys);
+
+    // must fail since the buyer could take the funds back right after the deposit
+    let res = escrow.send(SELLER, InitEscrow {
+        delivery_period: 0,
+        ..escrow_config()
+    });
     assert!(res.main_failed());
 }
 
+#[test]
+fn partial_deposit() {
+    let sys = System::new();
+    init_escrow(&sys);
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, 2*PRICE);
+
+    // the first part of the price
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE - 30_000);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::FundsPartiallyDeposited { remaining: 30_000 });
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingPayment);
+    assert_eq!(state.deposited, PRICE - 30_000);
+
+    // the top-up exceeds the remaining amount, the change is returned
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, 50_000);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::FundsDeposited);
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingDelivery);
+    assert_eq!(state.deposited, PRICE);
+    assert_eq!(sys.balance_of(ESCROW_ID), PRICE);
+
+    sys.claim_value_from_mailbox(BUYER);
+    assert_eq!(sys.balance_of(BUYER), PRICE);
+}
+
+#[test]
+fn cancel_partial_deposit() {
+    let sys = System::new();
+    init_escrow(&sys);
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, PRICE);
+
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, 40_000);
+    assert!(!res.main_failed());
+
+    // the partial deposit is returned on cancellation
+    let res = escrow.send(BUYER, EscrowAction::Cancel);
+    assert!(!res.main_failed());
+
+    sys.claim_value_from_mailbox(BUYER);
+    assert_eq!(sys.balance_of(BUYER), PRICE);
+}
+
 #[test]
 fn confirm_delivery() {
     let sys = System::new();
@@ -77,7 +306,7 @@ fn confirm_delivery() {
     assert!(res.main_failed());
 
     // successful deposit
-    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit, PRICE);
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
     assert!(!res.main_failed());
 
     // must fail since msg::source must be the buyer to confirm delivery
@@ -88,11 +317,1014 @@ fn confirm_delivery() {
     let res = escrow.send(BUYER, EscrowAction::ConfirmDelivery);
     let log = Log::builder()
         .dest(BUYER)
-        .payload(EscrowEvent::DeliveryConfirmed);
+        .payload(EscrowEvent::DeliveryConfirmed {
+            fee: 0,
+            net_to_seller: PRICE,
+        });
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    // claim value for the seller
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    assert!(!res.main_failed());
+    sys.claim_value_from_mailbox(SELLER);
+    assert_eq!(sys.balance_of(SELLER), PRICE);
+}
+#[test]
+fn tamagotchi_delivery() {
+    let sys = System::new();
+    init_tamagotchi_escrow(&sys);
+
+    let escrow = sys.get_program(ESCROW_ID);
+    let tamagotchi = sys.get_program(TAMAGOTCHI_ID);
+
+    sys.mint_to(BUYER, PRICE);
+
+    // the buyer gets the funds back since the escrow is not approved
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::TamagotchiNotDelivered(TAMAGOTCHI_ID.into()));
+    assert!(res.contains(&log));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingPayment);
+    sys.claim_value_from_mailbox(BUYER);
+    assert_eq!(sys.balance_of(BUYER), PRICE);
+
+    // the seller approves the escrow to transfer the tamagotchi
+    let res = tamagotchi.send(SELLER, TmgAction::Approve(ESCROW_ID.into()));
     assert!(!res.main_failed());
+
+    // successful deposit delivers the tamagotchi
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::TamagotchiDelivered(TAMAGOTCHI_ID.into()));
     assert!(res.contains(&log));
 
+    let state: Tamagotchi = tamagotchi.read_state().expect("Failed to read the state");
+    assert!(state.owner == BUYER.into());
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingWithdrawal);
+
+    // must fail since the escrow is closed
+    let res = escrow.send(BUYER, EscrowAction::ConfirmDelivery);
+    assert!(res.main_failed());
+
     // claim value for the seller
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    assert!(!res.main_failed());
+    sys.claim_value_from_mailbox(SELLER);
+    assert_eq!(sys.balance_of(SELLER), PRICE);
+}
+
+#[test]
+fn refund() {
+    let sys = System::new();
+    init_escrow(&sys);
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, PRICE);
+
+    // must fail since the state must be `AwaitingDelivery`
+    let res = escrow.send(BUYER, EscrowAction::Refund);
+    assert!(res.main_failed());
+
+    // successful deposit
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    assert!(!res.main_failed());
+
+    // must fail since the delivery deadline has not passed yet
+    let res = escrow.send(BUYER, EscrowAction::Refund);
+    assert!(res.main_failed());
+
+    sys.spend_blocks(DELIVERY_PERIOD);
+
+    // must fail since msg::source must be the buyer to get a refund
+    let res = escrow.send(SELLER, EscrowAction::Refund);
+    assert!(res.main_failed());
+
+    // successful refund
+    let res = escrow.send(BUYER, EscrowAction::Refund);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::Refunded);
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::Refunded);
+
+    // must fail since the escrow is refunded
+    let res = escrow.send(BUYER, EscrowAction::ConfirmDelivery);
+    assert!(res.main_failed());
+
+    // claim value for the buyer
+    sys.claim_value_from_mailbox(BUYER);
+    assert_eq!(sys.balance_of(BUYER), PRICE);
+}
+
+#[test]
+fn refund_after_shipment() {
+    let sys = System::new();
+    init_escrow(&sys);
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, PRICE);
+
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    assert!(!res.main_failed());
+
+    // the seller claims the shipment, but nothing arrives
+    let res = escrow.send(SELLER, EscrowAction::MarkShipped { tracking_hash: TRACKING_HASH });
+    assert!(!res.main_failed());
+
+    // must fail since the delivery deadline has not passed yet
+    let res = escrow.send(BUYER, EscrowAction::Refund);
+    assert!(res.main_failed());
+
+    sys.spend_blocks(DELIVERY_PERIOD);
+
+    let res = escrow.send(BUYER, EscrowAction::Refund);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::Refunded);
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    sys.claim_value_from_mailbox(BUYER);
+    assert_eq!(sys.balance_of(BUYER), PRICE);
+}
+
+#[test]
+fn cancel() {
+    let sys = System::new();
+    init_escrow(&sys);
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    // must fail since the sender is neither the buyer nor the seller
+    let res = escrow.send(42, EscrowAction::Cancel);
+    assert!(res.main_failed());
+
+    // successful cancellation
+    let res = escrow.send(SELLER, EscrowAction::Cancel);
+    let log = Log::builder()
+        .dest(SELLER)
+        .payload(EscrowEvent::Cancelled);
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::Cancelled);
+
+    // must fail since the state must be `AwaitingPayment`
+    sys.mint_to(BUYER, PRICE);
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    assert!(res.main_failed());
+}
+
+#[test]
+fn dispute() {
+    let sys = System::new();
+    init_escrow_with(&sys, InitEscrow {
+        arbiter: Some(ARBITER.into()),
+        arbiter_fee_bps: 500,
+        ..escrow_config()
+    });
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, PRICE);
+
+    // must fail since the state must be `AwaitingDelivery`
+    let res = escrow.send(BUYER, EscrowAction::OpenDispute);
+    assert!(res.main_failed());
+
+    // successful deposit
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    assert!(!res.main_failed());
+
+    // must fail since the sender is neither the buyer nor the seller
+    let res = escrow.send(ARBITER, EscrowAction::OpenDispute);
+    assert!(res.main_failed());
+
+    // successful dispute opening
+    let res = escrow.send(SELLER, EscrowAction::OpenDispute);
+    let log = Log::builder()
+        .dest(SELLER)
+        .payload(EscrowEvent::DisputeOpened(SELLER.into()));
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    // must fail since the funds are disputed
+    let res = escrow.send(BUYER, EscrowAction::ConfirmDelivery);
+    assert!(res.main_failed());
+
+    // must fail since msg::source must be the arbiter
+    let res = escrow.send(BUYER, EscrowAction::Resolve {
+        to_seller: 0,
+        to_buyer: PRICE,
+    });
+    assert!(res.main_failed());
+
+    // must fail since the split doesn't sum to the price
+    let res = escrow.send(ARBITER, EscrowAction::Resolve {
+        to_seller: PRICE,
+        to_buyer: PRICE,
+    });
+    assert!(res.main_failed());
+
+    // successful resolution
+    let res = escrow.send(ARBITER, EscrowAction::Resolve {
+        to_seller: 60_000,
+        to_buyer: 40_000,
+    });
+    let log = Log::builder()
+        .dest(ARBITER)
+        .payload(EscrowEvent::Resolved {
+            to_seller: 57_000,
+            to_buyer: 38_000,
+            fee: 5_000,
+            platform_fee: 0,
+        });
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::Resolved);
+
+    // claim value for all parties
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    assert!(!res.main_failed());
+    sys.claim_value_from_mailbox(SELLER);
+    sys.claim_value_from_mailbox(BUYER);
+    sys.claim_value_from_mailbox(ARBITER);
+    assert_eq!(sys.balance_of(SELLER), 57_000);
+    assert_eq!(sys.balance_of(BUYER), 38_000);
+    assert_eq!(sys.balance_of(ARBITER), 5_000);
+}
+
+#[test]
+fn dispute_milestones() {
+    let sys = System::new();
+    init_escrow_with(&sys, InitEscrow {
+        arbiter: Some(ARBITER.into()),
+        milestones: vec![
+            Milestone {
+                description_hash: [1; 32],
+                amount: 30_000,
+            },
+            Milestone {
+                description_hash: [2; 32],
+                amount: 70_000,
+            },
+        ],
+        ..escrow_config()
+    });
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, PRICE);
+
+    let res = escrow.send_with_value(BUYER, EscrowAction::FundMilestone(0), 30_000);
+    assert!(!res.main_failed());
+    let res = escrow.send(BUYER, EscrowAction::OpenDispute);
+    assert!(!res.main_failed());
+
+    // only the funded milestone is split
+    let res = escrow.send(ARBITER, EscrowAction::Resolve {
+        to_seller: 20_000,
+        to_buyer: 10_000,
+    });
+    assert!(!res.main_failed());
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::Resolved);
+    assert_eq!(state.milestones[0].1, MilestoneStatus::Released);
+    assert_eq!(state.milestones[1].1, MilestoneStatus::Pending);
+}
+
+#[test]
+fn auto_release() {
+    let sys = System::new();
+    init_escrow_with(&sys, InitEscrow {
+        auto_release_period: Some(AUTO_RELEASE_PERIOD),
+        ..escrow_config()
+    });
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, PRICE);
+
+    // successful deposit
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    assert!(!res.main_failed());
+
+    // must fail since msg::source must be the seller to mark shipment
+    let res = escrow.send(BUYER, EscrowAction::MarkShipped { tracking_hash: TRACKING_HASH });
+    assert!(res.main_failed());
+
+    // must fail since only the escrow itself can release the funds
+    let res = escrow.send(SELLER, EscrowAction::AutoRelease);
+    assert!(res.main_failed());
+
+    // successful shipment
+    let res = escrow.send(SELLER, EscrowAction::MarkShipped { tracking_hash: TRACKING_HASH });
+    let log = Log::builder()
+        .dest(SELLER)
+        .payload(EscrowEvent::Shipped);
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::Shipped);
+    assert_eq!(state.tracking_hash, Some(TRACKING_HASH));
+    assert!(state.shipped_at.is_some());
+
+    // the buyer stays silent, the funds are released to the seller
+    sys.spend_blocks(AUTO_RELEASE_PERIOD);
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingWithdrawal);
+
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    assert!(!res.main_failed());
     sys.claim_value_from_mailbox(SELLER);
     assert_eq!(sys.balance_of(SELLER), PRICE);
-}
\ No newline at end of file
+}
+
+#[test]
+fn auto_release_after_confirmation() {
+    let sys = System::new();
+    init_escrow_with(&sys, InitEscrow {
+        auto_release_period: Some(AUTO_RELEASE_PERIOD),
+        ..escrow_config()
+    });
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, PRICE);
+
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    assert!(!res.main_failed());
+    let res = escrow.send(SELLER, EscrowAction::MarkShipped { tracking_hash: TRACKING_HASH });
+    assert!(!res.main_failed());
+
+    // the buyer confirms the delivery before the funds are released
+    let res = escrow.send(BUYER, EscrowAction::ConfirmDelivery);
+    assert!(!res.main_failed());
+
+    // the delayed message is ignored since the escrow is closed
+    sys.spend_blocks(AUTO_RELEASE_PERIOD);
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingWithdrawal);
+
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    assert!(!res.main_failed());
+    sys.claim_value_from_mailbox(SELLER);
+    assert_eq!(sys.balance_of(SELLER), PRICE);
+}
+
+#[test]
+fn milestones() {
+    let sys = System::new();
+    init_escrow_with(&sys, InitEscrow {
+        milestones: vec![
+            Milestone {
+                description_hash: [1; 32],
+                amount: 30_000,
+            },
+            Milestone {
+                description_hash: [2; 32],
+                amount: 70_000,
+            },
+        ],
+        ..escrow_config()
+    });
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, PRICE);
+
+    // must fail since the attached value is not equal to the milestone amount
+    let res = escrow.send_with_value(BUYER, EscrowAction::FundMilestone(0), 70_000);
+    assert!(res.main_failed());
+
+    // must fail since the milestone doesn't exist
+    let res = escrow.send_with_value(BUYER, EscrowAction::FundMilestone(2), 30_000);
+    assert!(res.main_failed());
+
+    // successful funding of the first milestone
+    let res = escrow.send_with_value(BUYER, EscrowAction::FundMilestone(0), 30_000);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::MilestoneFunded(0));
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    // must fail since the second milestone is not funded
+    let res = escrow.send(BUYER, EscrowAction::ConfirmMilestone(1));
+    assert!(res.main_failed());
+
+    // successful confirmation of the first milestone
+    let res = escrow.send(BUYER, EscrowAction::ConfirmMilestone(0));
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::MilestoneReleased(0));
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    assert!(!res.main_failed());
+    sys.claim_value_from_mailbox(SELLER);
+    assert_eq!(sys.balance_of(SELLER), 30_000);
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingDelivery);
+    assert_eq!(state.milestones[0].1, MilestoneStatus::Released);
+    assert_eq!(state.milestones[1].1, MilestoneStatus::Pending);
+
+    // funding and confirming the second milestone closes the escrow
+    let res = escrow.send_with_value(BUYER, EscrowAction::FundMilestone(1), 70_000);
+    assert!(!res.main_failed());
+    let res = escrow.send(BUYER, EscrowAction::ConfirmMilestone(1));
+    assert!(!res.main_failed());
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingWithdrawal);
+
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    assert!(!res.main_failed());
+    sys.claim_value_from_mailbox(SELLER);
+    assert_eq!(sys.balance_of(SELLER), PRICE);
+}
+
+#[test]
+fn milestones_refund() {
+    let sys = System::new();
+    init_escrow_with(&sys, InitEscrow {
+        milestones: vec![
+            Milestone {
+                description_hash: [1; 32],
+                amount: 30_000,
+            },
+            Milestone {
+                description_hash: [2; 32],
+                amount: 70_000,
+            },
+        ],
+        ..escrow_config()
+    });
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, PRICE);
+
+    // funding everything up front
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    assert!(!res.main_failed());
+    let res = escrow.send(BUYER, EscrowAction::ConfirmMilestone(0));
+    assert!(!res.main_failed());
+
+    // only the unreleased milestone is refunded
+    sys.spend_blocks(DELIVERY_PERIOD);
+    let res = escrow.send(BUYER, EscrowAction::Refund);
+    assert!(!res.main_failed());
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::Refunded);
+    assert_eq!(state.milestones[1].1, MilestoneStatus::Refunded);
+
+    sys.claim_value_from_mailbox(BUYER);
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    assert!(!res.main_failed());
+    sys.claim_value_from_mailbox(SELLER);
+    assert_eq!(sys.balance_of(BUYER), 70_000);
+    assert_eq!(sys.balance_of(SELLER), 30_000);
+}
+
+#[test]
+fn fungible_token_deposit_failures() {
+    let sys = System::new();
+    init_escrow_with(&sys, InitEscrow {
+        currency: Currency::FungibleToken(FT_CONTRACT_ID.into()),
+        ..escrow_config()
+    });
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, PRICE);
+
+    // must fail since the escrow accepts fungible tokens only
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    assert!(res.main_failed());
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingPayment);
+    assert!(state.transactions.is_empty());
+
+    // must fail since there is no such transaction
+    let res = escrow.send(BUYER, EscrowAction::Continue(0));
+    assert!(res.main_failed());
+}
+
+#[test]
+fn platform_fee() {
+    let sys = System::new();
+    init_escrow_with(&sys, InitEscrow {
+        platform_fee: Some(PlatformFee {
+            recipient: PLATFORM.into(),
+            fee_bps: 250,
+        }),
+        ..escrow_config()
+    });
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, PRICE);
+
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    assert!(!res.main_failed());
+
+    // the fee is deducted from the seller payout
+    let res = escrow.send(BUYER, EscrowAction::ConfirmDelivery);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::DeliveryConfirmed {
+            fee: 2_500,
+            net_to_seller: 97_500,
+        });
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.fee, 2_500);
+    assert_eq!(state.net_to_seller, 97_500);
+
+    // claim value for the seller and the platform
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    assert!(!res.main_failed());
+    sys.claim_value_from_mailbox(SELLER);
+    sys.claim_value_from_mailbox(PLATFORM);
+    assert_eq!(sys.balance_of(SELLER), 97_500);
+    assert_eq!(sys.balance_of(PLATFORM), 2_500);
+}
+
+#[test]
+fn withdraw() {
+    let sys = System::new();
+    init_escrow(&sys);
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, PRICE);
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    assert!(!res.main_failed());
+
+    // must fail since nothing is owed yet
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    assert!(res.main_failed());
+
+    let res = escrow.send(BUYER, EscrowAction::ConfirmDelivery);
+    assert!(!res.main_failed());
+
+    // the proceeds stay in the escrow until the seller pulls them
+    assert_eq!(sys.balance_of(SELLER), 0);
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingWithdrawal);
+    assert_eq!(state.owed.get(&SELLER.into()), Some(&PRICE));
+
+    // must fail since the buyer is owed nothing
+    let res = escrow.send(BUYER, EscrowAction::Withdraw);
+    assert!(res.main_failed());
+
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    let log = Log::builder()
+        .dest(SELLER)
+        .payload(EscrowEvent::Withdrawn(PRICE));
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::Closed);
+    assert!(state.owed.is_empty());
+
+    // must fail since the funds are already withdrawn
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    assert!(res.main_failed());
+
+    sys.claim_value_from_mailbox(SELLER);
+    assert_eq!(sys.balance_of(SELLER), PRICE);
+}
+
+#[test]
+fn crowdfunding() {
+    let sys = System::new();
+    init_escrow_with(&sys, InitEscrow {
+        crowdfunding: Some(Crowdfunding {
+            contributors: Contributors::Shares(vec![
+                (BUYER.into(), 60_000),
+                (CO_BUYER.into(), 40_000),
+            ]),
+            funding_period: FUNDING_PERIOD,
+            quorum: 2,
+        }),
+        ..escrow_config()
+    });
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, 60_000);
+    sys.mint_to(CO_BUYER, 50_000);
+    sys.mint_to(SELLER, 10_000);
+
+    // must fail since the seller has no share
+    let res = escrow.send_with_value(SELLER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, 10_000);
+    assert!(res.main_failed());
+
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, 60_000);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::FundsPartiallyDeposited { remaining: 40_000 });
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    // the part exceeding the share is returned
+    let res = escrow.send_with_value(CO_BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, 50_000);
+    let log = Log::builder()
+        .dest(CO_BUYER)
+        .payload(EscrowEvent::FundsDeposited);
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+    sys.claim_value_from_mailbox(CO_BUYER);
+    assert_eq!(sys.balance_of(CO_BUYER), 10_000);
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingDelivery);
+
+    // the first confirmation isn't enough to release the funds
+    let res = escrow.send(BUYER, EscrowAction::ConfirmDelivery);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::ConfirmationRecorded { confirmations: 1, quorum: 2 });
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let res = escrow.send(CO_BUYER, EscrowAction::ConfirmDelivery);
+    assert!(!res.main_failed());
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingWithdrawal);
+
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    assert!(!res.main_failed());
+    sys.claim_value_from_mailbox(SELLER);
+    assert_eq!(sys.balance_of(SELLER), 10_000 + PRICE);
+}
+
+#[test]
+fn crowdfunding_refund() {
+    let sys = System::new();
+    init_escrow_with(&sys, InitEscrow {
+        crowdfunding: Some(Crowdfunding {
+            contributors: Contributors::Open,
+            funding_period: FUNDING_PERIOD,
+            quorum: 1,
+        }),
+        ..escrow_config()
+    });
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, 30_000);
+    sys.mint_to(CO_BUYER, 20_000);
+
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, 30_000);
+    assert!(!res.main_failed());
+    let res = escrow.send_with_value(CO_BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, 20_000);
+    assert!(!res.main_failed());
+
+    // must fail since the funding deadline has not passed yet
+    let res = escrow.send(CO_BUYER, EscrowAction::Refund);
+    assert!(res.main_failed());
+
+    sys.spend_blocks(FUNDING_PERIOD);
+
+    // must fail since the contributions are closed
+    let res = escrow.send_with_value(CO_BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, 10_000);
+    assert!(res.main_failed());
+
+    let res = escrow.send(CO_BUYER, EscrowAction::Refund);
+    let log = Log::builder()
+        .dest(CO_BUYER)
+        .payload(EscrowEvent::Refunded);
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::Refunded);
+
+    sys.claim_value_from_mailbox(BUYER);
+    sys.claim_value_from_mailbox(CO_BUYER);
+    assert_eq!(sys.balance_of(BUYER), 30_000);
+    assert_eq!(sys.balance_of(CO_BUYER), 20_000);
+}
+
+#[test]
+fn subscription() {
+    let sys = System::new();
+    init_escrow_with(&sys, InitEscrow {
+        subscription: Some(Subscription {
+            period: SUBSCRIPTION_PERIOD,
+            periods: SUBSCRIPTION_PERIODS,
+        }),
+        ..escrow_config()
+    });
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, PRICE);
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::SubscriptionStarted);
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    // must fail since only the escrow itself can release the periods
+    let res = escrow.send(SELLER, EscrowAction::ReleasePeriod);
+    assert!(res.main_failed());
+
+    sys.spend_blocks(SUBSCRIPTION_PERIOD);
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::Subscribed);
+    assert_eq!(state.periods_paid, 1);
+    assert_eq!(state.owed.get(&SELLER.into()), Some(&25_000));
+
+    sys.spend_blocks(SUBSCRIPTION_PERIOD * (SUBSCRIPTION_PERIODS - 1));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingWithdrawal);
+    assert_eq!(state.periods_paid, SUBSCRIPTION_PERIODS);
+    assert_eq!(state.next_release, None);
+
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    assert!(!res.main_failed());
+    sys.claim_value_from_mailbox(SELLER);
+    assert_eq!(sys.balance_of(SELLER), PRICE);
+}
+
+#[test]
+fn subscription_max_periods() {
+    let sys = System::new();
+    sys.init_logger();
+    let escrow = Program::current(&sys);
+    let price = MAX_PERIODS as u128 * 1_000;
+
+    // must fail since the deposit can't schedule that many releases
+    let res = escrow.send(SELLER, InitEscrow {
+        price: price + 1_000,
+        subscription: Some(Subscription {
+            period: SUBSCRIPTION_PERIOD,
+            periods: MAX_PERIODS + 1,
+        }),
+        ..escrow_config()
+    });
+    assert!(res.main_failed());
+
+    let sys = System::new();
+    init_escrow_with(&sys, InitEscrow {
+        price,
+        subscription: Some(Subscription {
+            period: SUBSCRIPTION_PERIOD,
+            periods: MAX_PERIODS,
+        }),
+        ..escrow_config()
+    });
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, price);
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, price);
+    assert!(!res.main_failed());
+
+    sys.spend_blocks(SUBSCRIPTION_PERIOD * MAX_PERIODS);
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.periods_paid, MAX_PERIODS);
+    assert_eq!(state.owed.get(&SELLER.into()), Some(&price));
+}
+
+#[test]
+fn subscription_cancel() {
+    let sys = System::new();
+    init_escrow_with(&sys, InitEscrow {
+        subscription: Some(Subscription {
+            period: SUBSCRIPTION_PERIOD,
+            periods: SUBSCRIPTION_PERIODS,
+        }),
+        ..escrow_config()
+    });
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    sys.mint_to(BUYER, PRICE);
+    let res = escrow.send_with_value(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH }, PRICE);
+    assert!(!res.main_failed());
+
+    sys.spend_blocks(SUBSCRIPTION_PERIOD);
+
+    // must fail since msg::source must be the buyer to cancel the subscription
+    let res = escrow.send(SELLER, EscrowAction::CancelSubscription);
+    assert!(res.main_failed());
+
+    let res = escrow.send(BUYER, EscrowAction::CancelSubscription);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::SubscriptionCancelled { refunded: 75_000 });
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    // the scheduled release is ignored after the cancellation
+    sys.spend_blocks(SUBSCRIPTION_PERIOD);
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::Cancelled);
+    assert_eq!(state.periods_paid, 1);
+
+    let res = escrow.send(SELLER, EscrowAction::Withdraw);
+    assert!(!res.main_failed());
+    sys.claim_value_from_mailbox(SELLER);
+    sys.claim_value_from_mailbox(BUYER);
+    assert_eq!(sys.balance_of(SELLER), 25_000);
+    assert_eq!(sys.balance_of(BUYER), 75_000);
+}
+
+#[test]
+fn on_behalf() {
+    let sys = System::new();
+    let escrow = Program::current(&sys);
+    let res = escrow.send(FACTORY_ID, InitEscrow {
+        from_factory: true,
+        ..escrow_config()
+    });
+    assert!(!res.main_failed());
+
+    sys.mint_to(FACTORY_ID, PRICE);
+    sys.mint_to(BUYER, PRICE);
+
+    // must fail since only the factory can act on behalf of the buyer
+    let res = escrow.send_with_value(SELLER, EscrowAction::OnBehalf {
+        account: BUYER.into(),
+        action: Box::new(EscrowAction::Deposit { terms_hash: TERMS_HASH }),
+    }, PRICE);
+    assert!(res.main_failed());
+
+    // must fail since the factory acts for the seller
+    let res = escrow.send_with_value(FACTORY_ID, EscrowAction::OnBehalf {
+        account: SELLER.into(),
+        action: Box::new(EscrowAction::Deposit { terms_hash: TERMS_HASH }),
+    }, PRICE);
+    assert!(res.main_failed());
+
+    let res = escrow.send_with_value(FACTORY_ID, EscrowAction::OnBehalf {
+        account: BUYER.into(),
+        action: Box::new(EscrowAction::Deposit { terms_hash: TERMS_HASH }),
+    }, PRICE);
+    let log = Log::builder()
+        .dest(FACTORY_ID)
+        .payload(EscrowEvent::FundsDeposited);
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.factory, Some(FACTORY_ID.into()));
+
+    // the parties can still use the escrow directly
+    let res = escrow.send(BUYER, EscrowAction::ConfirmDelivery);
+    assert!(!res.main_failed());
+
+    // the funds withdrawn through the factory go straight to the seller
+    let res = escrow.send(FACTORY_ID, EscrowAction::OnBehalf {
+        account: SELLER.into(),
+        action: Box::new(EscrowAction::Withdraw),
+    });
+    assert!(!res.main_failed());
+    sys.claim_value_from_mailbox(SELLER);
+    assert_eq!(sys.balance_of(SELLER), PRICE);
+    assert_eq!(sys.balance_of(FACTORY_ID), 0);
+}
+
+#[test]
+fn fungible_token_auto_release() {
+    let sys = System::new();
+    let ft = init_ft_escrow_with(&sys, InitEscrow {
+        auto_release_period: Some(AUTO_RELEASE_PERIOD),
+        ..escrow_config()
+    }, 0);
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    let res = escrow.send(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH });
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::FundsDeposited);
+    assert!(res.contains(&log));
+    assert_eq!(ft.balance_of(ESCROW_ID), PRICE);
+
+    let res = escrow.send(SELLER, EscrowAction::MarkShipped { tracking_hash: TRACKING_HASH });
+    assert!(!res.main_failed());
+
+    // the delayed release has enough gas for the token payout
+    sys.spend_blocks(AUTO_RELEASE_PERIOD);
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::Closed);
+    assert!(state.transactions.is_empty());
+    assert_eq!(ft.balance_of(SELLER), PRICE);
+    assert_eq!(ft.balance_of(ESCROW_ID), 0);
+}
+
+#[test]
+fn fungible_token_continue() {
+    let sys = System::new();
+    let ft = init_ft_escrow_with(&sys, escrow_config(), 1);
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    // the token doesn't reply, so the transfer is kept for a retry
+    let res = escrow.send(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH });
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::DepositFailed);
+    assert!(res.contains(&log));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingPayment);
+    assert_eq!(state.transactions.len(), 1);
+
+    // must fail since the escrow is waiting for the deposit
+    let res = escrow.send(BUYER, EscrowAction::Cancel);
+    assert!(res.main_failed());
+    let res = escrow.send(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH });
+    assert!(res.main_failed());
+
+    // the same transaction finishes the deposit
+    let res = escrow.send(BUYER, EscrowAction::Continue(0));
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(EscrowEvent::TransferCompleted(0));
+    assert!(res.contains(&log));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingDelivery);
+    assert!(state.transactions.is_empty());
+    assert_eq!(ft.balance_of(ESCROW_ID), PRICE);
+
+    let res = escrow.send(BUYER, EscrowAction::ConfirmDelivery);
+    assert!(!res.main_failed());
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::Closed);
+    assert_eq!(ft.balance_of(SELLER), PRICE);
+}
+
+#[test]
+fn fungible_token_failed_payout() {
+    let sys = System::new();
+    let ft = init_ft_escrow_with(&sys, escrow_config(), 0);
+
+    let escrow = sys.get_program(ESCROW_ID);
+
+    let res = escrow.send(BUYER, EscrowAction::Deposit { terms_hash: TERMS_HASH });
+    assert!(!res.main_failed());
+
+    // the token rejects the payout, the deal stays open
+    *ft.rejected.lock().unwrap() = 1;
+    let res = escrow.send(BUYER, EscrowAction::ConfirmDelivery);
+    assert!(!res.main_failed());
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::AwaitingWithdrawal);
+    assert_eq!(state.transactions.keys().copied().collect::<Vec<_>>(), vec![2]);
+
+    // must fail since the rejected transaction is replaced
+    let res = escrow.send(SELLER, EscrowAction::Continue(1));
+    assert!(res.main_failed());
+
+    let res = escrow.send(SELLER, EscrowAction::Continue(2));
+    let log = Log::builder()
+        .dest(SELLER)
+        .payload(EscrowEvent::TransferCompleted(2));
+    assert!(res.contains(&log));
+
+    let state: Escrow = escrow.read_state().expect("Failed to read the state");
+    assert_eq!(state.state, EscrowState::Closed);
+    assert_eq!(ft.balance_of(SELLER), PRICE);
+}
@@ -10,7 +10,7 @@ pub type TransactionId = u64;
 pub struct ProgramMetadata;
 
 impl Metadata for ProgramMetadata {
-   type Init = In<String>;
+   type Init = In<TmgInit>;
    type Reply = ();
    type Others = ();
    type Signal = ();
@@ -18,6 +18,13 @@ impl Metadata for ProgramMetadata {
    type State = Tamagotchi;
 }
 
+#[derive(Encode, Decode, TypeInfo)]
+pub struct TmgInit {
+   pub name: String,
+   /// Share of every sale paid to the creator, in basis points.
+   pub royalty_bps: Option<u16>,
+}
+
 #[derive(Encode, Decode, TypeInfo)]
 pub enum TmgAction {
    Name,
@@ -42,6 +49,13 @@ pub enum TmgAction {
       reservation_amount: u64,
       duration: u32,
    },
+   SetCareSettings(CareSettings),
+   ListForSale {
+      price: u128,
+   },
+   CancelListing,
+   Buy,
+   Owner,
 }
 
 #[derive(Encode, Decode, TypeInfo)]
@@ -65,6 +79,18 @@ pub enum TmgEvent {
    ErrorDuringPurchase,
    MakeReservation,
    GasReserved,
+   Overfed,
+   CareSettingsUpdated,
+   Listed { price: u128 },
+   ListingCancelled,
+   Sold {
+      seller: ActorId,
+      buyer: ActorId,
+      price: u128,
+      royalty: u128,
+   },
+   RoyaltyPaid(u128),
+   Owner(ActorId),
 }
 
 #[derive(Default, Encode, Decode, TypeInfo)]
@@ -85,6 +111,71 @@ pub struct Tamagotchi {
    pub ft_contract_id: ActorId,
 
    pub reservations: Vec<ReservationId>,
+
+   pub care: CareSettings,
+   pub overfed_until: u64,
+
+   pub creator: ActorId,
+   pub royalty_bps: u16,
+   pub listing: Option<u128>,
+}
+
+/// Describes how a single attribute (fed, entertained, rested)
+/// decays over time and how it is filled by the owner's care.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct SaturationCurve {
+   /// Hard cap of the attribute level.
+   pub max: u64,
+   /// Level lost per block.
+   pub decay_per_block: u64,
+   /// Level added by one action while the current level is below `soft_cap`.
+   pub fill: u64,
+   /// Level from which every action adds proportionally less,
+   /// reaching zero at `max`.
+   pub soft_cap: u64,
+   /// Level from which the action is rejected.
+   pub reject_at: u64,
+}
+
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct CareSettings {
+   pub fed: SaturationCurve,
+   pub entertained: SaturationCurve,
+   pub rested: SaturationCurve,
+   /// Fed level from which the tamagotchi becomes overfed.
+   pub overfed_at: u64,
+   /// Number of blocks the tamagotchi refuses to eat and play while overfed.
+   pub overfed_blocks: u64,
+}
+
+impl Default for CareSettings {
+   fn default() -> Self {
+      Self {
+         fed: SaturationCurve {
+            max: MAX_FED,
+            decay_per_block: HUNGER_PER_BLOCK,
+            fill: FILL_PER_FEED,
+            soft_cap: SOFT_CAP,
+            reject_at: REJECT_AT,
+         },
+         entertained: SaturationCurve {
+            max: MAX_HAPPY,
+            decay_per_block: BOREDOM_PER_BLOCK,
+            fill: FILL_PER_ENTERTAINMENT,
+            soft_cap: SOFT_CAP,
+            reject_at: REJECT_AT,
+         },
+         rested: SaturationCurve {
+            max: MAX_RESTED,
+            decay_per_block: ENERGY_PER_BLOCK,
+            fill: FILL_PER_SLEEP,
+            soft_cap: SOFT_CAP,
+            reject_at: REJECT_AT,
+         },
+         overfed_at: OVERFED_AT,
+         overfed_blocks: OVERFED_BLOCKS,
+      }
+   }
 }
 
 pub const HUNGER_PER_BLOCK: u64 = 1;
@@ -99,3 +190,9 @@ pub const MAX_FED: u64 = 10000;
 pub const MAX_HAPPY: u64 = 10000;
 pub const MAX_RESTED: u64 = 10000;
 
+pub const SOFT_CAP: u64 = 7000;
+pub const REJECT_AT: u64 = 9500;
+pub const OVERFED_AT: u64 = 9000;
+pub const OVERFED_BLOCKS: u64 = 100;
+
+pub const MAX_ROYALTY_BPS: u16 = 10_000;
\ No newline at end of file
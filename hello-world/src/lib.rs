@@ -32,8 +32,14 @@ trait NFTamagotchi {
     fn calculate_curr_rest(&mut self) -> u64;
     fn name(&mut self);
     fn age(&mut self);
+    fn owner(&mut self);
     fn assert_admin(&mut self);
     fn reserve_gas(&mut self, reservation_amount: u64, duration: u32);
+    fn set_care_settings(&mut self, settings: CareSettings);
+    fn is_overfed(&mut self) -> bool;
+    fn list_for_sale(&mut self, price: u128);
+    fn cancel_listing(&mut self);
+    fn buy(&mut self);
 }
 
 #[async_trait]
@@ -45,6 +51,7 @@ impl NFTamagotchi for Tamagotchi {
             "Only owner or allowed account can transfer ownership"
         );
         self.owner = actor_id;
+        self.listing = None;
 
         msg::reply(
             TmgEvent::Transfer(actor_id), 
@@ -200,15 +207,27 @@ impl NFTamagotchi for Tamagotchi {
 
     fn feed(&mut self) {
         assert!(msg::source() == self.owner, "Only owner can feed the tamagotchi");
-        assert!(self.fed < 7000, "Tamagotchi not enough hungry");
+        assert!(!self.is_overfed(), "Tamagotchi is overfed");
 
-        // calculating current hunger level
+        // calculating current fed level
         let curr_feed_level: u64 = self.calculate_curr_fed();
+        assert!(curr_feed_level < self.care.fed.reject_at, "Tamagotchi not enough hungry");
 
         // updating the state
-        self.fed = curr_feed_level + FILL_PER_FEED;
+        self.fed = saturate(&self.care.fed, curr_feed_level);
         self.fed_block = exec::block_height() as u64;
 
+        // too much food makes the tamagotchi sick for a while
+        if self.fed >= self.care.overfed_at {
+            self.overfed_until = exec::block_height() as u64 + self.care.overfed_blocks;
+
+            msg::reply(
+                TmgEvent::Overfed,
+                0
+            ).expect("Failed to share TmgEvent");
+            return;
+        }
+
         msg::reply(
             TmgEvent::Fed,
             0
@@ -216,33 +235,19 @@ impl NFTamagotchi for Tamagotchi {
     }
 
     fn calculate_curr_fed(&mut self) -> u64 {
-        // calculating and normalizing hunger level
-        let hunger_level = (exec::block_height() as u64 - self.fed_block) * HUNGER_PER_BLOCK;
-        let normalized_hunger_level = if hunger_level > MAX_FED {
-            MAX_FED
-        } else {
-            hunger_level
-        };
-
-        // calculating current hunger level
-        let curr_feed_level: u64 = if self.fed > normalized_hunger_level {
-            self.fed - normalized_hunger_level
-        } else {
-            0
-        };
-
-        return curr_feed_level;
+        current_level(&self.care.fed, self.fed, self.fed_block)
     }
 
     fn play(&mut self) {
         assert!(msg::source() == self.owner, "Only owner can feed the tamagotchi");
-        assert!(self.entertained < 7000, "Tamagotchi entertained enough");
+        assert!(!self.is_overfed(), "Tamagotchi is overfed");
 
         // calculating current happy level
         let curr_happy_level = self.calculate_curr_entertained();
+        assert!(curr_happy_level < self.care.entertained.reject_at, "Tamagotchi entertained enough");
 
         // updating the state
-        self.entertained = curr_happy_level + FILL_PER_ENTERTAINMENT;
+        self.entertained = saturate(&self.care.entertained, curr_happy_level);
         self.entertained_block = exec::block_height() as u64;
 
         msg::reply(
@@ -252,33 +257,18 @@ impl NFTamagotchi for Tamagotchi {
     }
 
     fn calculate_curr_entertained(&mut self) -> u64 {
-        // calculating and normalizing bored level
-        let bored_level: u64 = (exec::block_height() as u64 - self.entertained_block) * BOREDOM_PER_BLOCK;
-        let normalized_bored_level: u64 = if bored_level > MAX_HAPPY {
-            MAX_HAPPY
-        } else {
-            bored_level as u64
-        };
-
-        // calculating current happy level
-        let curr_happy_level = if self.entertained > normalized_bored_level {
-            self.entertained - normalized_bored_level
-        } else {
-            0
-        };
-
-        return curr_happy_level;
+        current_level(&self.care.entertained, self.entertained, self.entertained_block)
     }
 
     fn sleep(&mut self) {
         assert!(msg::source() == self.owner, "Only owner can feed the tamagotchi");
-        assert!(self.rested < 7000, "Tamagotchi don't wanna to sleep");
 
         // calculating current rested level
         let curr_rested_level = self.calculate_curr_rest();
+        assert!(curr_rested_level < self.care.rested.reject_at, "Tamagotchi don't wanna to sleep");
 
         // updating the state
-        self.rested = curr_rested_level + FILL_PER_SLEEP;
+        self.rested = saturate(&self.care.rested, curr_rested_level);
         self.rested_block = exec::block_height() as u64;
 
         msg::reply(
@@ -288,22 +278,7 @@ impl NFTamagotchi for Tamagotchi {
     }
 
     fn calculate_curr_rest(&mut self) -> u64 {
-        // calculating and normalizing energy loss
-        let energy_loss: u64 = (exec::block_height() as u64 - self.rested_block) * ENERGY_PER_BLOCK;
-        let normalized_energy_loss: u64 = if energy_loss > MAX_RESTED {
-            MAX_RESTED
-        } else {
-            energy_loss as u64
-        };
-
-        // calculating current rested level
-        let curr_rested_level = if self.rested > normalized_energy_loss {
-            self.rested - normalized_energy_loss
-        } else {
-            0
-        };
-
-        return curr_rested_level;
+        current_level(&self.care.rested, self.rested, self.rested_block)
     }
 
     fn name(&mut self) {
@@ -320,6 +295,13 @@ impl NFTamagotchi for Tamagotchi {
         ).expect("Failed to share the TmgEvent");
     }
 
+    fn owner(&mut self) {
+        msg::reply(
+            TmgEvent::Owner(self.owner),
+            0
+        ).expect("Failed to share the TmgEvent");
+    }
+
     fn assert_admin(&mut self) {
         assert_eq!(msg::source(), self.owner, "Only admin can send that message!")
     }
@@ -336,6 +318,105 @@ impl NFTamagotchi for Tamagotchi {
             0
         ).expect("Failed to share TmgEvent");
     }
+
+    fn set_care_settings(&mut self, settings: CareSettings) {
+        self.assert_admin();
+
+        for curve in [&settings.fed, &settings.entertained, &settings.rested] {
+            assert!(curve.soft_cap < curve.max, "Soft cap must be less than max level");
+            assert!(curve.reject_at <= curve.max, "Reject level must not exceed max level");
+        }
+
+        // levels are recalculated under the old curves before switching
+        self.fed = self.calculate_curr_fed().min(settings.fed.max);
+        self.entertained = self.calculate_curr_entertained().min(settings.entertained.max);
+        self.rested = self.calculate_curr_rest().min(settings.rested.max);
+
+        let block = exec::block_height() as u64;
+        self.fed_block = block;
+        self.entertained_block = block;
+        self.rested_block = block;
+        self.care = settings;
+
+        msg::reply(
+            TmgEvent::CareSettingsUpdated,
+            0
+        ).expect("Failed to share TmgEvent");
+    }
+
+    fn is_overfed(&mut self) -> bool {
+        (exec::block_height() as u64) < self.overfed_until
+    }
+
+    fn list_for_sale(&mut self, price: u128) {
+        self.assert_admin();
+        self.listing = Some(price);
+
+        msg::reply(
+            TmgEvent::Listed { price },
+            0
+        ).expect("Failed to share TmgEvent");
+    }
+
+    fn cancel_listing(&mut self) {
+        self.assert_admin();
+        assert!(self.listing.is_some(), "Tamagotchi is not listed for sale");
+        self.listing = None;
+
+        msg::reply(
+            TmgEvent::ListingCancelled,
+            0
+        ).expect("Failed to share TmgEvent");
+    }
+
+    fn buy(&mut self) {
+        let price = self.listing.expect("Tamagotchi is not listed for sale");
+        let buyer = msg::source();
+        let seller = self.owner;
+        assert_ne!(buyer, seller, "Owner can't buy own tamagotchi");
+        assert_eq!(
+            msg::value(),
+            price,
+            "The attached value must be equal to the listing price"
+        );
+
+        // no royalty is taken when the creator sells
+        let royalty = if seller != self.creator {
+            price * self.royalty_bps as u128 / MAX_ROYALTY_BPS as u128
+        } else {
+            0
+        };
+
+        // transferring ownership
+        self.owner = buyer;
+        self.allowed_account = None;
+        self.listing = None;
+
+        // sharing funds
+        if royalty != 0 {
+            msg::send(
+                self.creator,
+                TmgEvent::RoyaltyPaid(royalty),
+                royalty
+            ).expect("Failed to send royalty to the creator");
+        }
+        let event = TmgEvent::Sold {
+            seller,
+            buyer,
+            price,
+            royalty,
+        };
+        msg::send(
+            seller,
+            &event,
+            price - royalty
+        ).expect("Failed to send funds to the seller");
+
+        msg::reply(
+            event,
+            0
+        ).expect("Failed to share TmgEvent");
+    }
 }
 
 #[gstd::async_main]
@@ -352,6 +433,7 @@ async fn main() {
     match action {
         TmgAction::Age => tamagotchi.age(),
         TmgAction::Name => tamagotchi.name(),
+        TmgAction::Owner => tamagotchi.owner(),
         TmgAction::Feed => tamagotchi.feed(),
         TmgAction::Play => tamagotchi.play(),
         TmgAction::Sleep => tamagotchi.sleep(),
@@ -381,6 +463,10 @@ async fn main() {
             reservation_amount,
             duration
         } => tamagotchi.reserve_gas(reservation_amount, duration),
+        TmgAction::SetCareSettings(settings) => tamagotchi.set_care_settings(settings),
+        TmgAction::ListForSale { price } => tamagotchi.list_for_sale(price),
+        TmgAction::CancelListing => tamagotchi.cancel_listing(),
+        TmgAction::Buy => tamagotchi.buy(),
     };
 }
 
@@ -407,9 +493,12 @@ extern "C" fn my_handle_signal() {
 #[no_mangle]
 extern "C" fn init() {
     let owner = msg::source();
-    let name: String = String::from_utf8(
-        msg::load_bytes().expect("Can't load tamagotchi name")
-    ).expect("Can't decode tamagotchi name");
+    let TmgInit {
+        name,
+        royalty_bps,
+    } = msg::load().expect("Can't decode `TmgInit`");
+    let royalty_bps = royalty_bps.unwrap_or_default();
+    assert!(royalty_bps <= MAX_ROYALTY_BPS, "Royalty can't exceed 100%");
     let date_of_birth = exec::block_timestamp();
 
     let fed = INIT_ATTRIBUTE;
@@ -423,6 +512,10 @@ extern "C" fn init() {
     let ft_contract_id: ActorId = ActorId::zero();
     let transaction_id: u64 = 0;
     let reservations: Vec<ReservationId> = Vec::new();
+    let care = CareSettings::default();
+    let overfed_until: u64 = 0;
+    let creator = owner;
+    let listing: Option<u128> = None;
 
     unsafe { 
         TAMAGOTCHI = Some(Tamagotchi{
@@ -439,6 +532,11 @@ extern "C" fn init() {
             ft_contract_id,
             transaction_id,
             reservations,
+            care,
+            overfed_until,
+            creator,
+            royalty_bps,
+            listing,
         });
     };
 
@@ -465,4 +563,21 @@ extern "C" fn metahash() {
 
 fn reserve_gas() {
     exec::system_reserve_gas(1_000_000_000).expect("Error during system gas reservation");
+}
+
+// calculating current level of the attribute with the decay since `block`
+fn current_level(curve: &SaturationCurve, level: u64, block: u64) -> u64 {
+    let decay = (exec::block_height() as u64 - block).saturating_mul(curve.decay_per_block);
+    level.min(curve.max).saturating_sub(decay)
+}
+
+// filling the attribute with diminishing returns above the soft cap
+fn saturate(curve: &SaturationCurve, level: u64) -> u64 {
+    let fill = if level <= curve.soft_cap {
+        curve.fill
+    } else {
+        curve.fill.saturating_mul(curve.max.saturating_sub(level)) / (curve.max - curve.soft_cap)
+    };
+
+    level.saturating_add(fill).min(curve.max)
 }
\ No newline at end of file
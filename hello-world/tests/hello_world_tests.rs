@@ -9,7 +9,10 @@ const PROGRAM_ID: u64 = 1;
 fn init_tamagotchi(sys: &System) {
     sys.init_logger();
     let program = Program::current(&sys);
-    let res = program.send_bytes(OWNER, String::from("Satoshi"));
+    let res = program.send(OWNER, TmgInit {
+        name: String::from("Satoshi"),
+        royalty_bps: None,
+    });
     let expected_log = Log::builder()
         .dest(OWNER)
         .payload(String::from("Success!"));
@@ -186,3 +189,211 @@ fn tamagotchi_revoke_approval() {
     let state: Tamagotchi = program.read_state().expect("Failed to read the state");
     assert!(state.allowed_account.unwrap_or_default() != USER.into());
 }
+
+
+#[test]
+fn tamagotchi_saturation() {
+    // initialize env
+    let sys = System::new();
+
+    // initialize a contract, get program by id
+    init_tamagotchi(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+
+    // feed the tamagotchi until it becomes overfed
+    let overfed_log = Log::builder()
+        .dest(OWNER)
+        .payload(TmgEvent::Overfed);
+    let mut overfed = false;
+    for _ in 0..20 {
+        let res = program.send(OWNER, TmgAction::Feed);
+        assert!(!res.main_failed());
+
+        let state: Tamagotchi = program.read_state().expect("Error while reading the state");
+        assert!(state.fed <= MAX_FED, "Fed value exceeds the cap");
+
+        if res.contains(&overfed_log) {
+            overfed = true;
+            break;
+        }
+    }
+    assert!(overfed, "Tamagotchi must become overfed");
+
+    // check the state
+    let state: Tamagotchi = program.read_state().expect("Error while reading the state");
+    assert!(state.fed >= OVERFED_AT);
+    assert!(state.overfed_until != 0);
+
+    // must fail since tamagotchi is overfed
+    let res = program.send(OWNER, TmgAction::Feed);
+    assert!(res.main_failed());
+    let res = program.send(OWNER, TmgAction::Play);
+    assert!(res.main_failed());
+
+    // sleeping is still allowed
+    let res = program.send(OWNER, TmgAction::Sleep);
+    assert!(!res.main_failed());
+}
+
+#[test]
+fn tamagotchi_care_settings() {
+    // initialize env
+    let sys = System::new();
+
+    // initialize a contract, get program by id
+    init_tamagotchi(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+
+    let mut settings = CareSettings::default();
+    settings.fed.fill = 3000;
+    settings.fed.soft_cap = 2000;
+    settings.fed.max = 4000;
+    settings.fed.reject_at = 4000;
+    settings.overfed_at = 5000;
+
+    // must fail since USER is not owner
+    let res = program.send(USER, TmgAction::SetCareSettings(settings));
+    assert!(res.main_failed());
+
+    // must fail since soft cap is above the max level
+    let mut invalid_settings = settings;
+    invalid_settings.fed.soft_cap = 5000;
+    let res = program.send(OWNER, TmgAction::SetCareSettings(invalid_settings));
+    assert!(res.main_failed());
+
+    // successful update
+    let res = program.send(OWNER, TmgAction::SetCareSettings(settings));
+    let expected_log = Log::builder()
+        .dest(OWNER)
+        .payload(TmgEvent::CareSettingsUpdated);
+    assert!(!res.main_failed());
+    assert!(res.contains(&expected_log));
+
+    let state: Tamagotchi = program.read_state().expect("Error while reading the state");
+    assert!(state.care == settings);
+
+    // full fill below the soft cap: 500 -> 3500
+    let res = program.send(OWNER, TmgAction::Feed);
+    assert!(!res.main_failed());
+    let state: Tamagotchi = program.read_state().expect("Error while reading the state");
+    assert!(state.fed == 3500, "Invalid fed value");
+
+    // diminished fill above the soft cap: 3500 + 3000 * 500 / 2000 = 4250, clamped to 4000
+    let res = program.send(OWNER, TmgAction::Feed);
+    assert!(!res.main_failed());
+    let state: Tamagotchi = program.read_state().expect("Error while reading the state");
+    assert!(state.fed == 4000, "Fed value must be clamped to the cap");
+
+    // must fail since the level reached the reject level
+    let res = program.send(OWNER, TmgAction::Feed);
+    assert!(res.main_failed());
+}
+
+#[test]
+fn tamagotchi_sale() {
+    // initialize env
+    let sys = System::new();
+
+    // initialize a contract, get program by id
+    init_tamagotchi(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+
+    let price: u128 = 10_000;
+    sys.mint_to(USER, price);
+
+    // must fail since the tamagotchi is not listed
+    let res = program.send_with_value(USER, TmgAction::Buy, price);
+    assert!(res.main_failed());
+
+    // must fail since USER is not owner
+    let res = program.send(USER, TmgAction::ListForSale { price });
+    assert!(res.main_failed());
+
+    // successful listing
+    let res = program.send(OWNER, TmgAction::ListForSale { price });
+    let expected_log = Log::builder()
+        .dest(OWNER)
+        .payload(TmgEvent::Listed { price });
+    assert!(!res.main_failed());
+    assert!(res.contains(&expected_log));
+
+    let res = program.send(OWNER, TmgAction::Approve(USER2.into()));
+    assert!(!res.main_failed());
+
+    // must fail since the attached value is not equal to the price
+    let res = program.send_with_value(USER, TmgAction::Buy, price - 1);
+    assert!(res.main_failed());
+
+    // successful purchase
+    let res = program.send_with_value(USER, TmgAction::Buy, price);
+    let expected_log = Log::builder()
+        .dest(USER)
+        .payload(TmgEvent::Sold {
+            seller: OWNER.into(),
+            buyer: USER.into(),
+            price,
+            royalty: 0,
+        });
+    assert!(!res.main_failed());
+    assert!(res.contains(&expected_log));
+
+    // check the state after purchase
+    let state: Tamagotchi = program.read_state().expect("Failed to read the state");
+    assert!(state.owner == USER.into());
+    assert!(state.listing.is_none());
+    assert!(state.allowed_account.is_none());
+
+    // claim value for the seller
+    sys.claim_value_from_mailbox(OWNER);
+    assert_eq!(sys.balance_of(OWNER), price);
+}
+
+#[test]
+fn tamagotchi_sale_royalty() {
+    // initialize env
+    let sys = System::new();
+    sys.init_logger();
+
+    // initialize a contract with 10% royalty
+    let program = Program::current(&sys);
+    let res = program.send(OWNER, TmgInit {
+        name: String::from("Satoshi"),
+        royalty_bps: Some(1_000),
+    });
+    assert!(!res.main_failed());
+
+    let res = program.send(OWNER, TmgAction::Transfer(USER.into()));
+    assert!(!res.main_failed());
+
+    // listing and cancelling
+    let price: u128 = 10_000;
+    let res = program.send(USER, TmgAction::ListForSale { price });
+    assert!(!res.main_failed());
+    let res = program.send(USER, TmgAction::CancelListing);
+    let expected_log = Log::builder()
+        .dest(USER)
+        .payload(TmgEvent::ListingCancelled);
+    assert!(!res.main_failed());
+    assert!(res.contains(&expected_log));
+
+    // must fail since the listing is cancelled
+    sys.mint_to(USER2, price);
+    let res = program.send_with_value(USER2, TmgAction::Buy, price);
+    assert!(res.main_failed());
+
+    // successful purchase
+    let res = program.send(USER, TmgAction::ListForSale { price });
+    assert!(!res.main_failed());
+    let res = program.send_with_value(USER2, TmgAction::Buy, price);
+    assert!(!res.main_failed());
+
+    let state: Tamagotchi = program.read_state().expect("Failed to read the state");
+    assert!(state.owner == USER2.into());
+    assert!(state.creator == OWNER.into());
+
+    // claim value for the seller and the creator
+    sys.claim_value_from_mailbox(USER);
+    sys.claim_value_from_mailbox(OWNER);
+    assert_eq!(sys.balance_of(USER), price - price / 10);
+    assert_eq!(sys.balance_of(OWNER), price / 10);
+}
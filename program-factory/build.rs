@@ -1,3 +1,5 @@
+use factory_io::ProgramMetadata;
+
 fn main() {
-    gear_wasm_builder::build();
+    gear_wasm_builder::build_with_metadata::<ProgramMetadata>();
 }
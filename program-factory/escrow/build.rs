@@ -1,3 +0,0 @@
-fn main() {
-    gear_wasm_builder::build();
-}
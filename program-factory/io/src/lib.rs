@@ -0,0 +1,321 @@
+#![no_std]
+
+use escrow_io::{EscrowState, Milestone, MilestoneId, PlatformFee};
+use blake2::{digest::consts::U32, Blake2b, Digest};
+use gmeta::{In, InOut, Metadata};
+use gstd::{prelude::*, ActorId, CodeId};
+
+pub type EscrowId = u64;
+pub type TemplateVersion = u32;
+
+// the limits are sized for the escrow program, its milestones and payouts
+pub const DEFAULT_GAS_FOR_CREATION: u64 = 2_500_000_000;
+pub const DEFAULT_GAS_FOR_REPLY: u64 = 2_000_000_000;
+pub const MIN_GAS: u64 = 1_000_000_000;
+pub const MAX_GAS: u64 = 10_000_000_000;
+
+/// Salt the factory deploys with, so every creator has its own salt namespace.
+pub fn creator_salt(creator: ActorId, salt: &[u8]) -> Vec<u8> {
+    let mut creator_salt = creator.as_ref().to_vec();
+    creator_salt.extend_from_slice(salt);
+    creator_salt
+}
+
+pub struct ProgramMetadata;
+
+impl Metadata for ProgramMetadata {
+    type Init = In<InitFactory>;
+    type Handle = InOut<FactoryAction, FactoryEvent>;
+    type Reply = ();
+    type Others = ();
+    type Signal = ();
+    type State = EscrowFactory;
+}
+
+#[derive(Default, Encode, Decode, TypeInfo)]
+pub struct EscrowFactory {
+    pub escrow_number: EscrowId,
+    pub id_to_address: BTreeMap<EscrowId, ActorId>,
+    pub owner: ActorId,
+    /// Templates the escrows can be created from.
+    pub templates: BTreeMap<TemplateKind, EscrowTemplate>,
+    /// Template and its version every escrow was deployed with.
+    pub deployments: BTreeMap<EscrowId, (TemplateKind, TemplateVersion)>,
+    pub platform_fee: Option<PlatformFee>,
+    pub delivery_period: u32,
+    pub seller_escrows: BTreeMap<ActorId, Vec<EscrowId>>,
+    pub buyer_escrows: BTreeMap<ActorId, Vec<EscrowId>>,
+    pub address_to_id: BTreeMap<ActorId, EscrowId>,
+    /// Last state reported by every escrow.
+    pub statuses: BTreeMap<EscrowId, EscrowState>,
+    pub gas: GasConfig,
+    /// Salts used to create escrows, prefixed with the creator, see `creator_salt`.
+    pub salts: BTreeSet<Vec<u8>>,
+}
+
+/// Gas limits for deploying an escrow and for the messages awaiting its reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct GasConfig {
+    pub creation: u64,
+    pub reply: u64,
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        Self {
+            creation: DEFAULT_GAS_FOR_CREATION,
+            reply: DEFAULT_GAS_FOR_REPLY,
+        }
+    }
+}
+
+impl GasConfig {
+    pub fn is_within_bounds(&self) -> bool {
+        (MIN_GAS..=MAX_GAS).contains(&self.creation) && (MIN_GAS..=MAX_GAS).contains(&self.reply)
+    }
+}
+
+impl EscrowFactory {
+    /// Returns a page of the escrows where the account takes the role, oldest first.
+    pub fn escrows_of(
+        &self,
+        account: &ActorId,
+        role: Role,
+        offset: u32,
+        limit: u32,
+    ) -> Vec<(EscrowId, ActorId)> {
+        let index = match role {
+            Role::Seller => &self.seller_escrows,
+            Role::Buyer => &self.buyer_escrows,
+        };
+        index
+            .get(account)
+            .map(|ids| {
+                ids.iter()
+                    .skip(offset as usize)
+                    .take(limit as usize)
+                    .filter_map(|id| self.id_to_address.get(id).map(|address| (*id, *address)))
+                    .collect()
+            })
+            .unwrap_or_default()
+    }
+
+    /// Address of the escrow created by `creator` from the template with the salt.
+    /// It's the same way the runtime derives program ids.
+    pub fn predict_address(
+        &self,
+        creator: ActorId,
+        template: TemplateKind,
+        salt: &[u8],
+    ) -> Option<ActorId> {
+        let EscrowTemplate { code_id, .. } = self.templates.get(&template)?;
+        let mut hasher = Blake2b::<U32>::new();
+        hasher.update(code_id.as_ref());
+        hasher.update(creator_salt(creator, salt));
+        Some(ActorId::new(hasher.finalize().into()))
+    }
+
+    /// Returns a page of the escrows in the given state, oldest first.
+    pub fn escrows_with_status(
+        &self,
+        status: EscrowState,
+        offset: u32,
+        limit: u32,
+    ) -> Vec<(EscrowId, ActorId)> {
+        self.statuses
+            .iter()
+            .filter(|(_, escrow_status)| **escrow_status == status)
+            .skip(offset as usize)
+            .take(limit as usize)
+            .filter_map(|(id, _)| self.id_to_address.get(id).map(|address| (*id, *address)))
+            .collect()
+    }
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, PartialOrd, Ord, Encode, Decode, TypeInfo)]
+pub enum TemplateKind {
+    Simple,
+    Arbitrated,
+    Milestone,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct EscrowTemplate {
+    pub code_id: CodeId,
+    pub version: TemplateVersion,
+}
+
+/// Kind of the deal with its parameters, chosen on creation.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum Template {
+    /// The buyer confirms the delivery, no third party is involved.
+    Simple,
+    /// Disputes are resolved by the arbiter for a fee.
+    Arbitrated { arbiter: ActorId, arbiter_fee_bps: u16 },
+    /// The price is paid in parts.
+    Milestone(Vec<Milestone>),
+}
+
+impl Template {
+    pub fn kind(&self) -> TemplateKind {
+        match self {
+            Self::Simple => TemplateKind::Simple,
+            Self::Arbitrated { .. } => TemplateKind::Arbitrated,
+            Self::Milestone(_) => TemplateKind::Milestone,
+        }
+    }
+}
+
+/// Parameters of an escrow created by the factory.
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct EscrowParams {
+    pub seller: ActorId,
+    pub buyer: ActorId,
+    pub price: u128,
+    pub template: Template,
+    /// Overrides the configured gas within the bounds.
+    pub gas: Option<GasConfig>,
+    /// Makes the escrow address known in advance, see `predict_address`.
+    pub salt: Option<Vec<u8>>,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum CreationError {
+    TemplateNotAllowed,
+    GasOutOfBounds,
+    /// The caller has already used the salt.
+    DuplicateSalt,
+    /// The escrow program failed to initialize, e.g. due to insufficient gas.
+    InitFailed,
+}
+
+/// Why the escrow rejected the action sent through the factory.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum EscrowErrorReason {
+    /// The caller is not the buyer of the escrow.
+    NotBuyer,
+    /// The caller is neither the buyer nor the seller of the escrow.
+    NotParty,
+    /// The action is not allowed in the current state of the escrow.
+    WrongState(EscrowState),
+    /// The escrow failed the action for another reason, e.g. it ran out of gas.
+    Rejected,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum Role {
+    Seller,
+    Buyer,
+}
+
+#[derive(Encode, Decode, TypeInfo)]
+pub struct InitFactory {
+    /// Registered as the first version of every template.
+    pub escrow_code_id: CodeId,
+    /// Fee inherited by every escrow created by the factory.
+    pub platform_fee: Option<PlatformFee>,
+    /// Number of blocks the seller of every escrow has to deliver the goods.
+    /// Once it's passed the buyer can take the funds back with `Refund`.
+    pub delivery_period: u32,
+}
+
+#[derive(Encode, Decode, TypeInfo)]
+pub enum FactoryAction {
+    CreateEscrow {
+        seller: ActorId,
+        buyer: ActorId,
+        price: u128,
+        template: Template,
+        /// Overrides the configured gas within the bounds.
+        gas: Option<GasConfig>,
+        salt: Option<Vec<u8>>,
+    },
+    /// Deploys the escrows concurrently and reports the result of each one.
+    CreateEscrows(Vec<EscrowParams>),
+    Deposit(EscrowId),
+    ConfirmDelivery(EscrowId),
+    Refund(EscrowId),
+    Cancel(EscrowId),
+    OpenDispute(EscrowId),
+    FundMilestone {
+        escrow_id: EscrowId,
+        milestone_id: MilestoneId,
+    },
+    ConfirmMilestone {
+        escrow_id: EscrowId,
+        milestone_id: MilestoneId,
+    },
+    /// Claims the funds the escrow owes to the caller.
+    Withdraw(EscrowId),
+    EscrowsOf {
+        account: ActorId,
+        role: Role,
+        offset: u32,
+        limit: u32,
+    },
+    EscrowsWithStatus {
+        status: EscrowState,
+        offset: u32,
+        limit: u32,
+    },
+    /// Sent by the escrow itself whenever its state changes.
+    EscrowStateChanged(EscrowState),
+    /// Registers or upgrades the template code, owner only.
+    /// The version must be greater than the current one.
+    SetEscrowCode {
+        template: TemplateKind,
+        code_id: CodeId,
+        version: TemplateVersion,
+    },
+    /// Disallows creating escrows from the template, owner only.
+    RemoveTemplate(TemplateKind),
+    /// Updates the default gas, owner only.
+    SetGas(GasConfig),
+}
+
+#[derive(Encode, Decode, TypeInfo)]
+pub enum FactoryEvent {
+    EscrowCreated {
+        escrow_id: EscrowId,
+        escrow_address: ActorId,
+    },
+    Deposited(EscrowId),
+    /// The deposit was accepted, but doesn't cover the price yet.
+    PartiallyDeposited {
+        escrow_id: EscrowId,
+        remaining: u128,
+    },
+    DeliveryConfirmed(EscrowId),
+    Refunded(EscrowId),
+    Cancelled(EscrowId),
+    DisputeOpened(EscrowId),
+    MilestoneFunded {
+        escrow_id: EscrowId,
+        milestone_id: MilestoneId,
+    },
+    MilestoneReleased {
+        escrow_id: EscrowId,
+        milestone_id: MilestoneId,
+    },
+    Withdrawn {
+        escrow_id: EscrowId,
+        amount: u128,
+    },
+    EscrowsOf(Vec<(EscrowId, ActorId)>),
+    EscrowsWithStatus(Vec<(EscrowId, ActorId)>),
+    EscrowCodeSet {
+        template: TemplateKind,
+        code_id: CodeId,
+        version: TemplateVersion,
+    },
+    TemplateRemoved(TemplateKind),
+    EscrowCreationFailed,
+    /// Escrow id and address or the failure reason, in the order of the request.
+    EscrowsCreated(Vec<Result<(EscrowId, ActorId), CreationError>>),
+    GasUpdated(GasConfig),
+    /// The escrow rejected the action, the attached value is returned.
+    EscrowError {
+        escrow_id: EscrowId,
+        reason: EscrowErrorReason,
+    },
+}
@@ -1,64 +1,472 @@
+    async fn deposit(&self, escrow_id: EscrowId) {
+        // factory escrows are created without off-chain terms
+        let action = EscrowAction::Deposit {
+            terms_hash: Default::default(),
+        };
+        let Some(event) = self
+            .forward(escrow_id, &[Role::Buyer], &[EscrowState::AwaitingPayment], action)
+            .await
+        else {
+            return;
+        };
+
+        let event = match event {
+            EscrowEvent::FundsPartiallyDeposited { remaining } => FactoryEvent::PartiallyDeposited {
+                escrow_id,
+                remaining,
+            },
+            // the value has been forwarded already, so it isn't returned here
+            EscrowEvent::DepositFailed => FactoryEvent::EscrowError {
+                escrow_id,
+                reason: EscrowErrorReason::Rejected,
+            },
+            _ => FactoryEvent::Deposited(escrow_id),
+        };
+        msg::reply(event, 0).expect("Error during a reply `FactoryEvent`");
+    }
+
 #![no_std]
+use async_trait::async_trait;
+use futures::future::join_all;
 use escrow_io::*;
-use gstd::{msg, prelude::*, prog::ProgramGenerator, ActorId, CodeId};
+use factory_io::*;
+use gstd::{msg, prelude::*, prog::{self, ProgramGenerator}, ActorId, CodeId};
 
-pub const GAS_FOR_CREATION: u64 = 2_500_000_000;
-pub type EscrowId = u64;
+static mut ESCROW_FACTORY: Option<EscrowFactory> = None;
 
-#[derive(Default, Encode, Decode, TypeInfo)]
-pub struct EscrowFactory {
-    pub escrow_number: EscrowId,
-    pub id_to_address: BTreeMap<EscrowId, ActorId>,
-    pub escrow_code_id: CodeId,
+/// Escrow checked against the factory settings and ready to be deployed.
+struct PreparedEscrow {
+    seller: ActorId,
+    buyer: ActorId,
+    template: TemplateKind,
+    version: TemplateVersion,
+    code_id: CodeId,
+    payload: Vec<u8>,
+    gas_for_creation: u64,
+    salt: Option<Vec<u8>>,
 }
-static mut ESCROW_FACTORY: Option<EscrowFactory> = None;
 
-impl EscrowFactory {
-    async fn create_escrow(&mut self, seller: &ActorId, buyer: &ActorId, price: u128) {
-        let (address, _) = ProgramGenerator::create_program_with_gas_for_reply(
-            self.escrow_code_id,
-            InitEscrow {
-                seller: *seller,
-                buyer: *buyer,
-                price,
-            }
-            .encode(),
-            GAS_FOR_CREATION,
-            0,
-        )
-        .expect("Error during Escrow program initialization")
-        .await
-        .expect("Program was not initialized");
-    
-        self.escrow_number = self.escrow_number.saturating_add(1);
-        self.id_to_address.insert(self.escrow_number, address);
+#[async_trait]
+trait Factory {
+    async fn create_escrow(&mut self, params: EscrowParams);
+    async fn create_escrows(&mut self, escrows: Vec<EscrowParams>);
+    fn prepare_escrow(&mut self, params: EscrowParams) -> Result<PreparedEscrow, CreationError>;
+    fn register_escrow(&mut self, escrow: &PreparedEscrow, address: ActorId) -> EscrowId;
+    fn check_caller(
+        &self,
+        escrow_id: EscrowId,
+        roles: &[Role],
+        states: &[EscrowState],
+    ) -> Result<(), EscrowErrorReason>;
+    async fn forward(
+        &self,
+        escrow_id: EscrowId,
+        roles: &[Role],
+        states: &[EscrowState],
+        action: EscrowAction,
+    ) -> Option<EscrowEvent>;
+    async fn deposit(&self, escrow_id: EscrowId);
+    async fn confirm_delivery(&self, escrow_id: EscrowId);
+    async fn refund(&self, escrow_id: EscrowId);
+    async fn cancel(&self, escrow_id: EscrowId);
+    async fn open_dispute(&self, escrow_id: EscrowId);
+    async fn fund_milestone(&self, escrow_id: EscrowId, milestone_id: MilestoneId);
+    async fn confirm_milestone(&self, escrow_id: EscrowId, milestone_id: MilestoneId);
+    async fn withdraw(&self, escrow_id: EscrowId);
+    fn list_escrows(&self, account: &ActorId, role: Role, offset: u32, limit: u32);
+    fn list_escrows_with_status(&self, status: EscrowState, offset: u32, limit: u32);
+    fn update_status(&mut self, status: EscrowState);
+    fn set_escrow_code(&mut self, template: TemplateKind, code_id: CodeId, version: TemplateVersion);
+    fn remove_template(&mut self, template: TemplateKind);
+    fn set_gas(&mut self, gas: GasConfig);
+    fn get_escrow_address(&self, escrow_id: EscrowId) -> ActorId;
+}
+
+#[async_trait]
+impl Factory for EscrowFactory {
+    async fn create_escrow(&mut self, params: EscrowParams) {
+        let escrow = self
+            .prepare_escrow(params)
+            .unwrap_or_else(|error| panic!("Unable to create the escrow: {error:?}"));
+
+        let Ok((escrow, address)) = deploy(escrow).await else {
+            msg::reply(FactoryEvent::EscrowCreationFailed, 0)
+                .expect("Error during a reply `FactoryEvent::EscrowCreationFailed`");
+            return;
+        };
+
+        let escrow_id = self.register_escrow(&escrow, address);
+        if let Some(status) = query_status(address, self.gas.reply).await {
+            self.statuses.insert(escrow_id, status);
+        }
         msg::reply(
             FactoryEvent::EscrowCreated {
-                escrow_id: self.escrow_number,
+                escrow_id,
                 escrow_address: address,
             },
             0,
         )
         .expect("Error during a reply `FactoryEvent::ProgramCreated`");
     }
-    async fn deposit(&self, escrow_id: EscrowId) {
+
+    async fn create_escrows(&mut self, escrows: Vec<EscrowParams>) {
+        let prepared: Vec<_> = escrows
+            .into_iter()
+            .map(|params| self.prepare_escrow(params))
+            .collect();
+
+        // the escrows are deployed concurrently, only the initialized ones are registered
+        let deployed = join_all(prepared.into_iter().map(|escrow| async move {
+            match escrow {
+                Ok(escrow) => deploy(escrow).await,
+                Err(error) => Err(error),
+            }
+        }))
+        .await;
+        let results: Vec<_> = deployed
+            .into_iter()
+            .map(|result| {
+                result.map(|(escrow, address)| (self.register_escrow(&escrow, address), address))
+            })
+            .collect();
+        let gas = self.gas.reply;
+        let statuses = join_all(
+            results
+                .iter()
+                .flatten()
+                .map(|(_, address)| query_status(*address, gas)),
+        )
+        .await;
+        for ((escrow_id, _), status) in results.iter().flatten().zip(statuses) {
+            if let Some(status) = status {
+                self.statuses.insert(*escrow_id, status);
+            }
+        }
+
+        msg::reply(FactoryEvent::EscrowsCreated(results), 0)
+            .expect("Error during a reply `FactoryEvent::EscrowsCreated`");
+    }
+
+    fn prepare_escrow(&mut self, params: EscrowParams) -> Result<PreparedEscrow, CreationError> {
+        let EscrowParams {
+            seller,
+            buyer,
+            price,
+            template,
+            gas,
+            salt,
+        } = params;
+        let gas = gas.unwrap_or(self.gas);
+        if !gas.is_within_bounds() {
+            return Err(CreationError::GasOutOfBounds);
+        }
+        let kind = template.kind();
+        let EscrowTemplate { code_id, version } = *self
+            .templates
+            .get(&kind)
+            .ok_or(CreationError::TemplateNotAllowed)?;
+        // the address stays taken even if the initialization fails
+        let salt = salt.map(|salt| creator_salt(msg::source(), &salt));
+        if let Some(salt) = &salt {
+            if !self.salts.insert(salt.clone()) {
+                return Err(CreationError::DuplicateSalt);
+            }
+        }
+
+        let mut init = InitEscrow {
+            seller,
+            buyer,
+            price,
+            delivery_period: self.delivery_period,
+            platform_fee: self.platform_fee,
+            from_factory: true,
+            ..Default::default()
+        };
+        match template {
+            Template::Simple => {}
+            Template::Arbitrated {
+                arbiter,
+                arbiter_fee_bps,
+            } => {
+                init.arbiter = Some(arbiter);
+                init.arbiter_fee_bps = arbiter_fee_bps;
+            }
+            Template::Milestone(milestones) => init.milestones = milestones,
+        }
+
+        Ok(PreparedEscrow {
+            seller,
+            buyer,
+            template: kind,
+            version,
+            code_id,
+            payload: init.encode(),
+            gas_for_creation: gas.creation,
+            salt,
+        })
+    }
+
+    fn register_escrow(&mut self, escrow: &PreparedEscrow, address: ActorId) -> EscrowId {
+        self.escrow_number = self.escrow_number.saturating_add(1);
+        self.id_to_address.insert(self.escrow_number, address);
+        self.seller_escrows
+            .entry(escrow.seller)
+            .or_default()
+            .push(self.escrow_number);
+        self.buyer_escrows
+            .entry(escrow.buyer)
+            .or_default()
+            .push(self.escrow_number);
+        self.address_to_id.insert(address, self.escrow_number);
+        self.statuses.insert(self.escrow_number, EscrowState::AwaitingPayment);
+        self.deployments.insert(self.escrow_number, (escrow.template, escrow.version));
+        self.escrow_number
+    }
+
+    // the mirrored state tells why the escrow would reject the action,
+    // any state is allowed if `states` is empty, `forward` rechecks a wrong state
+    fn check_caller(
+        &self,
+        escrow_id: EscrowId,
+        roles: &[Role],
+        states: &[EscrowState],
+    ) -> Result<(), EscrowErrorReason> {
+        let source = msg::source();
+        let has_role = roles.iter().any(|role| {
+            let escrows = match role {
+                Role::Seller => &self.seller_escrows,
+                Role::Buyer => &self.buyer_escrows,
+            };
+            escrows
+                .get(&source)
+                .map_or(false, |escrows| escrows.contains(&escrow_id))
+        });
+        if !has_role {
+            return Err(match roles {
+                [Role::Buyer] => EscrowErrorReason::NotBuyer,
+                _ => EscrowErrorReason::NotParty,
+            });
+        }
+
+        let status = self.statuses.get(&escrow_id).copied().unwrap_or_default();
+        if !states.is_empty() && !states.contains(&status) {
+            return Err(EscrowErrorReason::WrongState(status));
+        }
+        Ok(())
+    }
+
+    async fn forward(
+        &self,
+        escrow_id: EscrowId,
+        roles: &[Role],
+        states: &[EscrowState],
+        action: EscrowAction,
+    ) -> Option<EscrowEvent> {
         let escrow_address = self.get_escrow_address(escrow_id);
-        send_message(&escrow_address, EscrowAction::Deposit(msg::source())).await;
+        // the mirrored state may lag behind, so the escrow is asked before rejecting
+        let checked = match self.check_caller(escrow_id, roles, states) {
+            Err(EscrowErrorReason::WrongState(_)) => {
+                match query_status(escrow_address, self.gas.reply).await {
+                    Some(status) if states.contains(&status) => Ok(()),
+                    Some(status) => Err(EscrowErrorReason::WrongState(status)),
+                    None => Err(EscrowErrorReason::Rejected),
+                }
+            }
+            checked => checked,
+        };
+        let result = match checked {
+            Ok(()) => send_message(&escrow_address, self.gas.reply, on_behalf(action)).await,
+            Err(reason) => Err(reason),
+        };
+        match result {
+            Ok(event) => Some(event),
+            Err(reason) => {
+                reply_escrow_error(escrow_id, reason);
+                None
+            }
+        }
+    }
+
+    async fn deposit(&self, escrow_id: EscrowId) {
+        // factory escrows are created without off-chain terms
+        let action = EscrowAction::Deposit {
+            terms_hash: Default::default(),
+        };
+        if self
+            .forward(escrow_id, &[Role::Buyer], &[EscrowState::AwaitingPayment], action)
+            .await
+            .is_none()
+        {
+            return;
+        }
 
         msg::reply(FactoryEvent::Deposited(escrow_id), 0)
             .expect("Error during a reply `FactoryEvent::Deposited`");
     }
+
     async fn confirm_delivery(&self, escrow_id: EscrowId) {
-        let escrow_address = self.get_escrow_address(escrow_id);
-        send_message(
-            &escrow_address,
-            EscrowAction::ConfirmDelivery(msg::source()),
-        )
-        .await;
+        let states = [EscrowState::AwaitingDelivery, EscrowState::Shipped];
+        if self
+            .forward(escrow_id, &[Role::Buyer], &states, EscrowAction::ConfirmDelivery)
+            .await
+            .is_none()
+        {
+            return;
+        }
+
         msg::reply(FactoryEvent::DeliveryConfirmed(escrow_id), 0)
             .expect("Error during a reply `FactoryEvent::DeliveryConfirmed`");
     }
 
+    async fn refund(&self, escrow_id: EscrowId) {
+        let states = [EscrowState::AwaitingDelivery, EscrowState::Shipped];
+        if self
+            .forward(escrow_id, &[Role::Buyer], &states, EscrowAction::Refund)
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        msg::reply(FactoryEvent::Refunded(escrow_id), 0)
+            .expect("Error during a reply `FactoryEvent::Refunded`");
+    }
+
+    async fn cancel(&self, escrow_id: EscrowId) {
+        let roles = [Role::Seller, Role::Buyer];
+        if self
+            .forward(escrow_id, &roles, &[EscrowState::AwaitingPayment], EscrowAction::Cancel)
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        msg::reply(FactoryEvent::Cancelled(escrow_id), 0)
+            .expect("Error during a reply `FactoryEvent::Cancelled`");
+    }
+
+    async fn open_dispute(&self, escrow_id: EscrowId) {
+        let roles = [Role::Seller, Role::Buyer];
+        let states = [EscrowState::AwaitingDelivery, EscrowState::Shipped];
+        if self
+            .forward(escrow_id, &roles, &states, EscrowAction::OpenDispute)
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        msg::reply(FactoryEvent::DisputeOpened(escrow_id), 0)
+            .expect("Error during a reply `FactoryEvent::DisputeOpened`");
+    }
+
+    async fn fund_milestone(&self, escrow_id: EscrowId, milestone_id: MilestoneId) {
+        let states = [
+            EscrowState::AwaitingPayment,
+            EscrowState::AwaitingDelivery,
+            EscrowState::Shipped,
+        ];
+        let action = EscrowAction::FundMilestone(milestone_id);
+        if self
+            .forward(escrow_id, &[Role::Buyer], &states, action)
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        msg::reply(FactoryEvent::MilestoneFunded { escrow_id, milestone_id }, 0)
+            .expect("Error during a reply `FactoryEvent::MilestoneFunded`");
+    }
+
+    async fn confirm_milestone(&self, escrow_id: EscrowId, milestone_id: MilestoneId) {
+        let states = [EscrowState::AwaitingDelivery, EscrowState::Shipped];
+        let action = EscrowAction::ConfirmMilestone(milestone_id);
+        if self
+            .forward(escrow_id, &[Role::Buyer], &states, action)
+            .await
+            .is_none()
+        {
+            return;
+        }
+
+        msg::reply(FactoryEvent::MilestoneReleased { escrow_id, milestone_id }, 0)
+            .expect("Error during a reply `FactoryEvent::MilestoneReleased`");
+    }
+
+    // the escrow sends the withdrawn funds to the caller directly
+    async fn withdraw(&self, escrow_id: EscrowId) {
+        let roles = [Role::Seller, Role::Buyer];
+        let Some(EscrowEvent::Withdrawn(amount)) = self
+            .forward(escrow_id, &roles, &[], EscrowAction::Withdraw)
+            .await
+        else {
+            return;
+        };
+
+        msg::reply(FactoryEvent::Withdrawn { escrow_id, amount }, 0)
+            .expect("Error during a reply `FactoryEvent::Withdrawn`");
+    }
+
+    fn list_escrows(&self, account: &ActorId, role: Role, offset: u32, limit: u32) {
+        let escrows = self.escrows_of(account, role, offset, limit);
+        msg::reply(FactoryEvent::EscrowsOf(escrows), 0)
+            .expect("Error during a reply `FactoryEvent::EscrowsOf`");
+    }
+
+    fn list_escrows_with_status(&self, status: EscrowState, offset: u32, limit: u32) {
+        let escrows = self.escrows_with_status(status, offset, limit);
+        msg::reply(FactoryEvent::EscrowsWithStatus(escrows), 0)
+            .expect("Error during a reply `FactoryEvent::EscrowsWithStatus`");
+    }
+
+    // reports sent before the registration are ignored,
+    // the state is queried once the escrow is registered
+    fn update_status(&mut self, status: EscrowState) {
+        if let Some(escrow_id) = self.address_to_id.get(&msg::source()) {
+            self.statuses.insert(*escrow_id, status);
+        }
+    }
+
+    fn set_escrow_code(&mut self, template: TemplateKind, code_id: CodeId, version: TemplateVersion) {
+        assert_eq!(msg::source(), self.owner, "Only the owner can set the escrow code");
+        if let Some(current) = self.templates.get(&template) {
+            assert!(
+                version > current.version,
+                "The version must be greater than the current one"
+            );
+        }
+
+        self.templates.insert(template, EscrowTemplate { code_id, version });
+        msg::reply(
+            FactoryEvent::EscrowCodeSet {
+                template,
+                code_id,
+                version,
+            },
+            0,
+        )
+        .expect("Error during a reply `FactoryEvent::EscrowCodeSet`");
+    }
+
+    fn remove_template(&mut self, template: TemplateKind) {
+        assert_eq!(msg::source(), self.owner, "Only the owner can remove templates");
+        // the escrows already deployed from the template keep working
+        self.templates.remove(&template).expect("The template is not registered");
+        msg::reply(FactoryEvent::TemplateRemoved(template), 0)
+            .expect("Error during a reply `FactoryEvent::TemplateRemoved`");
+    }
+
+    fn set_gas(&mut self, gas: GasConfig) {
+        assert_eq!(msg::source(), self.owner, "Only the owner can set the gas");
+        assert!(gas.is_within_bounds(), "Gas is out of bounds");
+
+        self.gas = gas;
+        msg::reply(FactoryEvent::GasUpdated(gas), 0)
+            .expect("Error during a reply `FactoryEvent::GasUpdated`");
+    }
+
     fn get_escrow_address(&self, escrow_id: EscrowId) -> ActorId {
         *self
             .id_to_address
@@ -77,47 +485,156 @@ async fn main() {
             seller,
             buyer,
             price,
-        } => factory.create_escrow(&seller, &buyer, price).await,
+            template,
+            gas,
+            salt,
+        } => {
+            factory
+                .create_escrow(EscrowParams {
+                    seller,
+                    buyer,
+                    price,
+                    template,
+                    gas,
+                    salt,
+                })
+                .await
+        }
+        FactoryAction::CreateEscrows(escrows) => factory.create_escrows(escrows).await,
         FactoryAction::Deposit(escrow_id) => factory.deposit(escrow_id).await,
         FactoryAction::ConfirmDelivery(escrow_id) => factory.confirm_delivery(escrow_id).await,
+        FactoryAction::Refund(escrow_id) => factory.refund(escrow_id).await,
+        FactoryAction::Cancel(escrow_id) => factory.cancel(escrow_id).await,
+        FactoryAction::OpenDispute(escrow_id) => factory.open_dispute(escrow_id).await,
+        FactoryAction::FundMilestone {
+            escrow_id,
+            milestone_id,
+        } => factory.fund_milestone(escrow_id, milestone_id).await,
+        FactoryAction::ConfirmMilestone {
+            escrow_id,
+            milestone_id,
+        } => factory.confirm_milestone(escrow_id, milestone_id).await,
+        FactoryAction::Withdraw(escrow_id) => factory.withdraw(escrow_id).await,
+        FactoryAction::EscrowsOf {
+            account,
+            role,
+            offset,
+            limit,
+        } => factory.list_escrows(&account, role, offset, limit),
+        FactoryAction::EscrowsWithStatus {
+            status,
+            offset,
+            limit,
+        } => factory.list_escrows_with_status(status, offset, limit),
+        FactoryAction::EscrowStateChanged(status) => factory.update_status(status),
+        FactoryAction::SetEscrowCode {
+            template,
+            code_id,
+            version,
+        } => factory.set_escrow_code(template, code_id, version),
+        FactoryAction::RemoveTemplate(template) => factory.remove_template(template),
+        FactoryAction::SetGas(gas) => factory.set_gas(gas),
     }
 }
 
 #[no_mangle]
 extern "C" fn init() {
-    let escrow_code_id: CodeId =
-        msg::load().expect("Unable to decode CodeId of the Escrow program");
-    let escrow_factory = EscrowFactory {
+    let InitFactory {
         escrow_code_id,
+        platform_fee,
+        delivery_period,
+    } = msg::load().expect("Unable to decode `InitFactory`");
+    assert!(delivery_period != 0, "Delivery period must be non-zero");
+    let template = EscrowTemplate {
+        code_id: escrow_code_id,
+        version: 1,
+    };
+    let escrow_factory = EscrowFactory {
+        owner: msg::source(),
+        templates: [
+            (TemplateKind::Simple, template),
+            (TemplateKind::Arbitrated, template),
+            (TemplateKind::Milestone, template),
+        ]
+        .into(),
+        platform_fee,
+        delivery_period,
         ..Default::default()
     };
     unsafe { ESCROW_FACTORY = Some(escrow_factory) };
 }
 
-#[derive(Encode, Decode, TypeInfo)]
-pub enum FactoryAction {
-    CreateEscrow {
-        seller: ActorId,
-        buyer: ActorId,
-        price: u128,
-    },
-    Deposit(EscrowId),
-    ConfirmDelivery(EscrowId),
+async fn deploy(escrow: PreparedEscrow) -> Result<(PreparedEscrow, ActorId), CreationError> {
+    let future = match &escrow.salt {
+        Some(salt) => prog::create_program_with_gas_for_reply(
+            escrow.code_id,
+            salt,
+            &escrow.payload,
+            escrow.gas_for_creation,
+            0,
+        ),
+        None => ProgramGenerator::create_program_with_gas_for_reply(
+            escrow.code_id,
+            &escrow.payload,
+            escrow.gas_for_creation,
+            0,
+        ),
+    };
+    let (address, _) = future
+        .map_err(|_| CreationError::InitFailed)?
+        .await
+        .map_err(|_| CreationError::InitFailed)?;
+    Ok((escrow, address))
+}
+
+// the escrow could be used before the registration, e.g. at the predicted address
+async fn query_status(address: ActorId, gas: u64) -> Option<EscrowState> {
+    let result = msg::send_with_gas_for_reply_as::<_, EscrowEvent>(
+        address,
+        EscrowAction::State,
+        gas,
+        0,
+    )
+    .expect("Error during a sending message to a Escrow program")
+    .await;
+    match result {
+        Ok(EscrowEvent::State(status)) => Some(status),
+        _ => None,
+    }
 }
 
-#[derive(Encode, Decode, TypeInfo)]
-pub enum FactoryEvent {
-    EscrowCreated {
-        escrow_id: EscrowId,
-        escrow_address: ActorId,
-    },
-    Deposited(EscrowId),
-    DeliveryConfirmed(EscrowId),
+fn on_behalf(action: EscrowAction) -> EscrowAction {
+    EscrowAction::OnBehalf {
+        account: msg::source(),
+        action: Box::new(action),
+    }
 }
 
-async fn send_message(escrow_address: &ActorId, escrow_payload: EscrowAction) {
-    msg::send_for_reply_as::<_, EscrowEvent>(*escrow_address, escrow_payload, msg::value())
+async fn send_message(
+    escrow_address: &ActorId,
+    gas: u64,
+    escrow_payload: EscrowAction,
+) -> Result<EscrowEvent, EscrowErrorReason> {
+    msg::send_with_gas_for_reply_as::<_, EscrowEvent>(*escrow_address, escrow_payload, gas, msg::value())
         .expect("Error during a sending message to a Escrow program")
         .await
-        .expect("Unable to decode EscrowEvent");
+        .map_err(|_| EscrowErrorReason::Rejected)
+}
+
+// the value bounced back by the failed escrow is returned to the caller
+fn reply_escrow_error(escrow_id: EscrowId, reason: EscrowErrorReason) {
+    msg::reply(FactoryEvent::EscrowError { escrow_id, reason }, msg::value())
+        .expect("Error during a reply `FactoryEvent::EscrowError`");
+}
+
+#[no_mangle]
+extern "C" fn state() {
+    let factory = unsafe { ESCROW_FACTORY.get_or_insert(Default::default()) };
+    msg::reply(factory, 0).expect("Failed to share state");
+}
+
+#[no_mangle]
+extern "C" fn metahash() {
+    let metahash: [u8; 32] = include!("../.metahash");
+    msg::reply(metahash, 0).expect("Failed to share metahash");
 }
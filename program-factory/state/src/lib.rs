@@ -0,0 +1,64 @@
+#![no_std]
+use escrow_io::EscrowState;
+use factory_io::*;
+use gmeta::metawasm;
+use gstd::{prelude::*, ActorId};
+
+#[metawasm]
+pub mod metafns {
+    pub type State = EscrowFactory;
+
+    pub fn escrow_address(state: State, escrow_id: EscrowId) -> Option<ActorId> {
+        state.id_to_address.get(&escrow_id).copied()
+    }
+
+    pub fn escrow_count(state: State) -> EscrowId {
+        state.escrow_number
+    }
+
+    pub fn escrows_of(state: State, query: (ActorId, Role, u32, u32)) -> Vec<(EscrowId, ActorId)> {
+        let (account, role, offset, limit) = query;
+        state.escrows_of(&account, role, offset, limit)
+    }
+
+    pub fn escrow_count_of(state: State, query: (ActorId, Role)) -> u32 {
+        let (account, role) = query;
+        let index = match role {
+            Role::Seller => state.seller_escrows,
+            Role::Buyer => state.buyer_escrows,
+        };
+        index.get(&account).map_or(0, |ids| ids.len() as u32)
+    }
+
+    pub fn escrow_status(state: State, escrow_id: EscrowId) -> Option<EscrowState> {
+        state.statuses.get(&escrow_id).copied()
+    }
+
+    pub fn escrows_with_status(
+        state: State,
+        query: (EscrowState, u32, u32),
+    ) -> Vec<(EscrowId, ActorId)> {
+        let (status, offset, limit) = query;
+        state.escrows_with_status(status, offset, limit)
+    }
+
+    pub fn template(state: State, template: TemplateKind) -> Option<EscrowTemplate> {
+        state.templates.get(&template).copied()
+    }
+
+    pub fn deployment(state: State, escrow_id: EscrowId) -> Option<(TemplateKind, TemplateVersion)> {
+        state.deployments.get(&escrow_id).copied()
+    }
+
+    pub fn predict_address(
+        state: State,
+        query: (ActorId, TemplateKind, Vec<u8>),
+    ) -> Option<ActorId> {
+        let (creator, template, salt) = query;
+        state.predict_address(creator, template, &salt)
+    }
+
+    pub fn escrows(state: State) -> Vec<(EscrowId, ActorId)> {
+        state.id_to_address.into_iter().collect()
+    }
+}
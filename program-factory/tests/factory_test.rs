@@ -1,14 +1,21 @@
 use gtest::{Program, System, Log};
-use program_factory::*;
+use escrow_io::{EscrowState, Milestone};
+use factory_io::*;
 
 const OWNER: u64 = 100;
 const BUYER: u64 = 101;
+const ARBITER: u64 = 102;
 const PROGRAM_ID: u64 = 1;
+const DELIVERY_PERIOD: u32 = 10;
 
 fn init_escrow_factory(sys: &System) {
-    let escrow_code_id = sys.submit_code("./escrow/target/wasm32-unknown-unknown/debug/escrow.opt.wasm");
+    let escrow_code_id = sys.submit_code("../escrow/target/wasm32-unknown-unknown/debug/escrow.opt.wasm");
     let escrow_factory = Program::current(&sys);
-    let res = escrow_factory.send(OWNER, escrow_code_id);
+    let res = escrow_factory.send(OWNER, InitFactory {
+        escrow_code_id,
+        platform_fee: None,
+        delivery_period: DELIVERY_PERIOD,
+    });
 
     assert!(!res.main_failed());
     assert!(res.log().is_empty());
@@ -27,7 +34,10 @@ fn create_escrow() {
     let mut res = program.send(OWNER, FactoryAction::CreateEscrow{
         seller: OWNER.into(),
         buyer: BUYER.into(),
-        price: price 
+        price: price,
+        template: Template::Simple,
+        gas: None,
+        salt: None,
     });
     assert!(!res.main_failed());
     assert!(!res.log().is_empty());
@@ -37,10 +47,17 @@ fn create_escrow() {
     res = program.send(OWNER, FactoryAction::CreateEscrow{
         seller: BUYER.into(),
         buyer: OWNER.into(),
-        price: price 
+        price: price,
+        template: Template::Simple,
+        gas: None,
+        salt: None,
     });
     assert!(!res.main_failed());
     assert!(!res.log().is_empty());
+
+    let state: EscrowFactory = program.read_state().expect("Failed to read the state");
+    assert_eq!(state.escrow_number, 2);
+    assert_eq!(state.id_to_address.len(), 2);
 }
 
 #[test]
@@ -56,7 +73,10 @@ fn deposit_confirm_escrow() {
     let _ = program.send(OWNER, FactoryAction::CreateEscrow{
         seller: OWNER.into(),
         buyer: BUYER.into(),
-        price: price 
+        price: price,
+        template: Template::Simple,
+        gas: None,
+        salt: None,
     });
 
     // send deposit action
@@ -80,4 +100,594 @@ fn deposit_confirm_escrow() {
     assert!(!res.main_failed());
     assert!(!res.log().is_empty());
     assert!(res.contains(&expected_log));
-}
\ No newline at end of file
+
+    // the escrow has reported its state to the factory
+    let state: EscrowFactory = program.read_state().expect("Failed to read the state");
+    assert_eq!(state.statuses.get(&escrow_id), Some(&EscrowState::Closed));
+
+    let res = program.send(BUYER, FactoryAction::EscrowsWithStatus {
+        status: EscrowState::Closed,
+        offset: 0,
+        limit: 10,
+    });
+    let expected_log = Log::builder()
+        .dest(BUYER)
+        .payload(FactoryEvent::EscrowsWithStatus(vec![(
+            escrow_id,
+            state.id_to_address[&escrow_id],
+        )]));
+    assert!(!res.main_failed());
+    assert!(res.contains(&expected_log));
+
+    // only the registered escrows can report their state
+    let res = program.send(BUYER, FactoryAction::EscrowStateChanged(EscrowState::Cancelled));
+    assert!(!res.main_failed());
+    let state: EscrowFactory = program.read_state().expect("Failed to read the state");
+    assert_eq!(state.statuses.get(&escrow_id), Some(&EscrowState::Closed));
+}
+#[test]
+fn refund_escrow() {
+    let sys = System::new();
+
+    init_escrow_factory(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+
+    let price: u128 = 1_000;
+    let res = program.send(OWNER, FactoryAction::CreateEscrow {
+        seller: OWNER.into(),
+        buyer: BUYER.into(),
+        price,
+        template: Template::Simple,
+        gas: None,
+        salt: None,
+    });
+    assert!(!res.main_failed());
+
+    sys.mint_to(BUYER, price);
+    let res = program.send_with_value(BUYER, FactoryAction::Deposit(1), price);
+    assert!(!res.main_failed());
+
+    // the seller doesn't deliver in time
+    sys.spend_blocks(DELIVERY_PERIOD);
+
+    let res = program.send(BUYER, FactoryAction::Refund(1));
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(FactoryEvent::Refunded(1));
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    sys.claim_value_from_mailbox(BUYER);
+    assert_eq!(sys.balance_of(BUYER), price);
+
+    let state: EscrowFactory = program.read_state().expect("Failed to read the state");
+    assert_eq!(state.statuses.get(&1), Some(&EscrowState::Refunded));
+}
+
+#[test]
+fn escrows_of() {
+    let sys = System::new();
+
+    init_escrow_factory(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+
+    for price in [100, 200, 300] {
+        let res = program.send(OWNER, FactoryAction::CreateEscrow {
+            seller: OWNER.into(),
+            buyer: BUYER.into(),
+            price,
+            template: Template::Simple,
+            gas: None,
+            salt: None,
+        });
+        assert!(!res.main_failed());
+    }
+    let res = program.send(OWNER, FactoryAction::CreateEscrow {
+        seller: BUYER.into(),
+        buyer: OWNER.into(),
+        price: 400,
+        template: Template::Simple,
+        gas: None,
+        salt: None,
+    });
+    assert!(!res.main_failed());
+
+    let state: EscrowFactory = program.read_state().expect("Failed to read the state");
+    let address = |id: EscrowId| state.id_to_address[&id];
+
+    // the second page of the seller's escrows
+    let res = program.send(BUYER, FactoryAction::EscrowsOf {
+        account: OWNER.into(),
+        role: Role::Seller,
+        offset: 2,
+        limit: 2,
+    });
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(FactoryEvent::EscrowsOf(vec![(3, address(3))]));
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let res = program.send(BUYER, FactoryAction::EscrowsOf {
+        account: OWNER.into(),
+        role: Role::Buyer,
+        offset: 0,
+        limit: 10,
+    });
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(FactoryEvent::EscrowsOf(vec![(4, address(4))]));
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+}
+
+#[test]
+fn escrow_error() {
+    let sys = System::new();
+
+    init_escrow_factory(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+
+    let price: u128 = 1_000;
+    let res = program.send(OWNER, FactoryAction::CreateEscrow {
+        seller: OWNER.into(),
+        buyer: BUYER.into(),
+        price,
+        template: Template::Simple,
+        gas: None,
+        salt: None,
+    });
+    assert!(!res.main_failed());
+
+    // the deposit from the seller is rejected, the value is returned
+    sys.mint_to(OWNER, price);
+    let res = program.send_with_value(OWNER, FactoryAction::Deposit(1), price);
+    let log = Log::builder()
+        .dest(OWNER)
+        .payload(FactoryEvent::EscrowError {
+            escrow_id: 1,
+            reason: EscrowErrorReason::NotBuyer,
+        });
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+    sys.claim_value_from_mailbox(OWNER);
+    assert_eq!(sys.balance_of(OWNER), price);
+
+    // the confirmation before the deposit is rejected
+    let res = program.send(BUYER, FactoryAction::ConfirmDelivery(1));
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(FactoryEvent::EscrowError {
+            escrow_id: 1,
+            reason: EscrowErrorReason::WrongState(EscrowState::AwaitingPayment),
+        });
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let state: EscrowFactory = program.read_state().expect("Failed to read the state");
+    assert_eq!(state.statuses.get(&1), Some(&EscrowState::AwaitingPayment));
+}
+
+#[test]
+fn templates() {
+    let sys = System::new();
+
+    init_escrow_factory(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+    let escrow_code_id = sys.submit_code("../escrow/target/wasm32-unknown-unknown/debug/escrow.opt.wasm");
+
+    // must fail since only the owner can upgrade the code
+    let res = program.send(BUYER, FactoryAction::SetEscrowCode {
+        template: TemplateKind::Arbitrated,
+        code_id: escrow_code_id,
+        version: 2,
+    });
+    assert!(res.main_failed());
+
+    // must fail since the version must grow
+    let res = program.send(OWNER, FactoryAction::SetEscrowCode {
+        template: TemplateKind::Arbitrated,
+        code_id: escrow_code_id,
+        version: 1,
+    });
+    assert!(res.main_failed());
+
+    let res = program.send(OWNER, FactoryAction::SetEscrowCode {
+        template: TemplateKind::Arbitrated,
+        code_id: escrow_code_id,
+        version: 2,
+    });
+    assert!(!res.main_failed());
+
+    let res = program.send(OWNER, FactoryAction::CreateEscrow {
+        seller: OWNER.into(),
+        buyer: BUYER.into(),
+        price: 1_000,
+        template: Template::Arbitrated {
+            arbiter: ARBITER.into(),
+            arbiter_fee_bps: 500,
+        },
+        gas: None,
+        salt: None,
+    });
+    assert!(!res.main_failed());
+
+    let res = program.send(OWNER, FactoryAction::RemoveTemplate(TemplateKind::Milestone));
+    let log = Log::builder()
+        .dest(OWNER)
+        .payload(FactoryEvent::TemplateRemoved(TemplateKind::Milestone));
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    // must fail since the template is removed
+    let res = program.send(OWNER, FactoryAction::CreateEscrow {
+        seller: OWNER.into(),
+        buyer: BUYER.into(),
+        price: 1_000,
+        template: Template::Milestone(vec![]),
+        gas: None,
+        salt: None,
+    });
+    assert!(res.main_failed());
+
+    let state: EscrowFactory = program.read_state().expect("Failed to read the state");
+    assert_eq!(state.owner, OWNER.into());
+    assert_eq!(state.deployments.get(&1), Some(&(TemplateKind::Arbitrated, 2)));
+    assert!(!state.templates.contains_key(&TemplateKind::Milestone));
+}
+
+#[test]
+fn creation_gas() {
+    let sys = System::new();
+
+    init_escrow_factory(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+
+    let gas = GasConfig {
+        creation: 3_000_000_000,
+        reply: DEFAULT_GAS_FOR_REPLY,
+    };
+
+    // must fail since only the owner can set the gas
+    let res = program.send(BUYER, FactoryAction::SetGas(gas));
+    assert!(res.main_failed());
+
+    let res = program.send(OWNER, FactoryAction::SetGas(gas));
+    let log = Log::builder()
+        .dest(OWNER)
+        .payload(FactoryEvent::GasUpdated(gas));
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    // must fail since the override exceeds the bounds
+    let res = program.send(OWNER, FactoryAction::CreateEscrow {
+        seller: OWNER.into(),
+        buyer: BUYER.into(),
+        price: 1_000,
+        template: Template::Simple,
+        gas: Some(GasConfig {
+            creation: MAX_GAS + 1,
+            reply: DEFAULT_GAS_FOR_REPLY,
+        }),
+        salt: None,
+    });
+    assert!(res.main_failed());
+
+    let state: EscrowFactory = program.read_state().expect("Failed to read the state");
+    assert_eq!(state.gas, gas);
+    assert_eq!(state.escrow_number, 0);
+}
+
+#[test]
+fn create_escrows() {
+    let sys = System::new();
+
+    init_escrow_factory(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+
+    let params = |price: u128, gas: Option<GasConfig>| EscrowParams {
+        seller: OWNER.into(),
+        buyer: BUYER.into(),
+        price,
+        template: Template::Simple,
+        gas,
+        salt: None,
+    };
+    let out_of_bounds = GasConfig {
+        creation: MIN_GAS - 1,
+        reply: DEFAULT_GAS_FOR_REPLY,
+    };
+
+    let res = program.send(OWNER, FactoryAction::CreateEscrows(vec![
+        params(100, None),
+        params(200, Some(out_of_bounds)),
+        params(300, None),
+    ]));
+    assert!(!res.main_failed());
+
+    let state: EscrowFactory = program.read_state().expect("Failed to read the state");
+    assert_eq!(state.escrow_number, 2);
+
+    let log = Log::builder()
+        .dest(OWNER)
+        .payload(FactoryEvent::EscrowsCreated(vec![
+            Ok((1, state.id_to_address[&1])),
+            Err(CreationError::GasOutOfBounds),
+            Ok((2, state.id_to_address[&2])),
+        ]));
+    assert!(res.contains(&log));
+}
+
+#[test]
+fn low_creation_gas() {
+    let sys = System::new();
+
+    init_escrow_factory(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+
+    // the gas is within the bounds, but not enough to initialize the escrow
+    let low = GasConfig {
+        creation: MIN_GAS,
+        reply: DEFAULT_GAS_FOR_REPLY,
+    };
+    let res = program.send(OWNER, FactoryAction::CreateEscrow {
+        seller: OWNER.into(),
+        buyer: BUYER.into(),
+        price: 100,
+        template: Template::Simple,
+        gas: Some(low),
+        salt: None,
+    });
+    let log = Log::builder()
+        .dest(OWNER)
+        .payload(FactoryEvent::EscrowCreationFailed);
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let res = program.send(OWNER, FactoryAction::CreateEscrows(vec![EscrowParams {
+        seller: OWNER.into(),
+        buyer: BUYER.into(),
+        price: 100,
+        template: Template::Simple,
+        gas: Some(low),
+        salt: None,
+    }]));
+    let log = Log::builder()
+        .dest(OWNER)
+        .payload(FactoryEvent::EscrowsCreated(vec![Err(CreationError::InitFailed)]));
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let state: EscrowFactory = program.read_state().expect("Failed to read the state");
+    assert_eq!(state.escrow_number, 0);
+}
+
+#[test]
+fn salted_escrow() {
+    let sys = System::new();
+
+    init_escrow_factory(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+
+    let salt = b"deal-1".to_vec();
+    let state: EscrowFactory = program.read_state().expect("Failed to read the state");
+    let predicted = state
+        .predict_address(OWNER.into(), TemplateKind::Simple, &salt)
+        .expect("The template is not registered");
+
+    let res = program.send(OWNER, FactoryAction::CreateEscrow {
+        seller: OWNER.into(),
+        buyer: BUYER.into(),
+        price: 1_000,
+        template: Template::Simple,
+        gas: None,
+        salt: Some(salt.clone()),
+    });
+    let log = Log::builder()
+        .dest(OWNER)
+        .payload(FactoryEvent::EscrowCreated {
+            escrow_id: 1,
+            escrow_address: predicted,
+        });
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    // must fail since the salt is already used
+    let res = program.send(OWNER, FactoryAction::CreateEscrow {
+        seller: OWNER.into(),
+        buyer: BUYER.into(),
+        price: 1_000,
+        template: Template::Simple,
+        gas: None,
+        salt: Some(salt.clone()),
+    });
+    assert!(res.main_failed());
+
+    let res = program.send(OWNER, FactoryAction::CreateEscrows(vec![EscrowParams {
+        seller: OWNER.into(),
+        buyer: BUYER.into(),
+        price: 1_000,
+        template: Template::Simple,
+        gas: None,
+        salt: Some(salt.clone()),
+    }]));
+    let log = Log::builder()
+        .dest(OWNER)
+        .payload(FactoryEvent::EscrowsCreated(vec![Err(CreationError::DuplicateSalt)]));
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    // another caller reusing the salt gets its own address
+    let predicted_for_buyer = state
+        .predict_address(BUYER.into(), TemplateKind::Simple, &salt)
+        .expect("The template is not registered");
+    assert_ne!(predicted_for_buyer, predicted);
+
+    let res = program.send(BUYER, FactoryAction::CreateEscrow {
+        seller: OWNER.into(),
+        buyer: BUYER.into(),
+        price: 1_000,
+        template: Template::Simple,
+        gas: None,
+        salt: Some(salt),
+    });
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(FactoryEvent::EscrowCreated {
+            escrow_id: 2,
+            escrow_address: predicted_for_buyer,
+        });
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+}
+
+#[test]
+fn escrow_actions() {
+    let sys = System::new();
+
+    init_escrow_factory(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+
+    let price: u128 = 1_000;
+    let templates = [
+        Template::Simple,
+        Template::Simple,
+        Template::Arbitrated {
+            arbiter: ARBITER.into(),
+            arbiter_fee_bps: 0,
+        },
+        Template::Milestone(vec![
+            Milestone {
+                description_hash: [1; 32],
+                amount: 400,
+            },
+            Milestone {
+                description_hash: [2; 32],
+                amount: 600,
+            },
+        ]),
+    ];
+    for template in templates {
+        let res = program.send(OWNER, FactoryAction::CreateEscrow {
+            seller: OWNER.into(),
+            buyer: BUYER.into(),
+            price,
+            template,
+            gas: None,
+            salt: None,
+        });
+        assert!(!res.main_failed());
+    }
+    sys.mint_to(BUYER, 3 * price);
+
+    // the seller withdraws the proceeds through the factory
+    let res = program.send_with_value(BUYER, FactoryAction::Deposit(1), price);
+    assert!(!res.main_failed());
+    let res = program.send(BUYER, FactoryAction::ConfirmDelivery(1));
+    assert!(!res.main_failed());
+
+    let res = program.send(ARBITER, FactoryAction::Withdraw(1));
+    let log = Log::builder()
+        .dest(ARBITER)
+        .payload(FactoryEvent::EscrowError {
+            escrow_id: 1,
+            reason: EscrowErrorReason::NotParty,
+        });
+    assert!(res.contains(&log));
+
+    let res = program.send(OWNER, FactoryAction::Withdraw(1));
+    let log = Log::builder()
+        .dest(OWNER)
+        .payload(FactoryEvent::Withdrawn {
+            escrow_id: 1,
+            amount: price,
+        });
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+    sys.claim_value_from_mailbox(OWNER);
+    assert_eq!(sys.balance_of(OWNER), price);
+
+    let res = program.send(OWNER, FactoryAction::Cancel(2));
+    let log = Log::builder()
+        .dest(OWNER)
+        .payload(FactoryEvent::Cancelled(2));
+    assert!(res.contains(&log));
+
+    let res = program.send_with_value(BUYER, FactoryAction::Deposit(3), price);
+    assert!(!res.main_failed());
+    let res = program.send(BUYER, FactoryAction::OpenDispute(3));
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(FactoryEvent::DisputeOpened(3));
+    assert!(res.contains(&log));
+
+    let res = program.send_with_value(BUYER, FactoryAction::FundMilestone {
+        escrow_id: 4,
+        milestone_id: 0,
+    }, 400);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(FactoryEvent::MilestoneFunded {
+            escrow_id: 4,
+            milestone_id: 0,
+        });
+    assert!(res.contains(&log));
+
+    let res = program.send(BUYER, FactoryAction::ConfirmMilestone {
+        escrow_id: 4,
+        milestone_id: 0,
+    });
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(FactoryEvent::MilestoneReleased {
+            escrow_id: 4,
+            milestone_id: 0,
+        });
+    assert!(res.contains(&log));
+
+    let state: EscrowFactory = program.read_state().expect("Failed to read the state");
+    assert_eq!(state.statuses.get(&1), Some(&EscrowState::Closed));
+    assert_eq!(state.statuses.get(&2), Some(&EscrowState::Cancelled));
+    assert_eq!(state.statuses.get(&3), Some(&EscrowState::Disputed));
+    assert_eq!(state.statuses.get(&4), Some(&EscrowState::AwaitingDelivery));
+}
+
+#[test]
+fn partial_deposit() {
+    let sys = System::new();
+
+    init_escrow_factory(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+
+    let price: u128 = 1_000;
+    let res = program.send(OWNER, FactoryAction::CreateEscrow {
+        seller: OWNER.into(),
+        buyer: BUYER.into(),
+        price,
+        template: Template::Simple,
+        gas: None,
+        salt: None,
+    });
+    assert!(!res.main_failed());
+    sys.mint_to(BUYER, price);
+
+    let res = program.send_with_value(BUYER, FactoryAction::Deposit(1), 400);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(FactoryEvent::PartiallyDeposited {
+            escrow_id: 1,
+            remaining: 600,
+        });
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let res = program.send_with_value(BUYER, FactoryAction::Deposit(1), 600);
+    let log = Log::builder()
+        .dest(BUYER)
+        .payload(FactoryEvent::Deposited(1));
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let state: EscrowFactory = program.read_state().expect("Failed to read the state");
+    assert_eq!(state.statuses.get(&1), Some(&EscrowState::AwaitingDelivery));
+}
@@ -6,6 +6,12 @@ use scale_info::TypeInfo;
 
 pub type TamagotchiId = u64;
 
+// the limits are sized for the tamagotchi program, which is far smaller than an escrow
+pub const DEFAULT_GAS_FOR_CREATION: u64 = 2_500_000_000;
+pub const DEFAULT_GAS_FOR_REPLY: u64 = 2_000_000_000;
+pub const MIN_GAS: u64 = 1_000_000_000;
+pub const MAX_GAS: u64 = 5_000_000_000;
+
 pub struct ProgramMetadata;
 
 impl Metadata for ProgramMetadata {
@@ -22,11 +28,41 @@ pub struct TmgArmy {
     pub tmg_number: TamagotchiId,
     pub id_to_address: BTreeMap<TamagotchiId, ActorId>,
     pub tmg_code_id: CodeId,
+    pub owner: ActorId,
+    pub gas: GasConfig,
+}
+
+/// Gas limits for deploying a tamagotchi and for the messages awaiting its reply.
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct GasConfig {
+    pub creation: u64,
+    pub reply: u64,
+}
+
+impl Default for GasConfig {
+    fn default() -> Self {
+        Self {
+            creation: DEFAULT_GAS_FOR_CREATION,
+            reply: DEFAULT_GAS_FOR_REPLY,
+        }
+    }
+}
+
+impl GasConfig {
+    pub fn is_within_bounds(&self) -> bool {
+        (MIN_GAS..=MAX_GAS).contains(&self.creation) && (MIN_GAS..=MAX_GAS).contains(&self.reply)
+    }
 }
 
 #[derive(Encode, Decode, TypeInfo)]
 pub enum ArmyAction {
-    CreateTamagotchi(String),
+    CreateTamagotchi {
+        name: String,
+        /// Overrides the configured gas within the bounds.
+        gas: Option<GasConfig>,
+    },
+    /// Updates the default gas, owner only.
+    SetGas(GasConfig),
 }
 
 #[derive(Encode, Decode, TypeInfo)]
@@ -36,4 +72,6 @@ pub enum ArmyEvent {
         tamagotchi_address: ActorId,
     },
     TamagotchiNotTransfered(ActorId),
+    TamagotchiCreationFailed,
+    GasUpdated(GasConfig),
 }
\ No newline at end of file
@@ -4,34 +4,42 @@ use async_trait::async_trait;
 use tmg_army_io::*;
 use hello_world_io::*;
 
-pub const GAS_FOR_CREATION: u64 = 2_500_000_000;
-
 static mut TAMAGOTCHI_ARMY: Option<TmgArmy> = None;
 
 #[async_trait]
 pub trait Army {
-    async fn create_tamagotchi(&mut self, owner: &ActorId, name: &String);
+    async fn create_tamagotchi(&mut self, owner: &ActorId, name: &String, gas: Option<GasConfig>);
+    fn set_gas(&mut self, gas: GasConfig);
 }
 
 #[async_trait]
 impl Army for TmgArmy {
-    async fn create_tamagotchi(&mut self, owner: &ActorId, name: &String) {
-        // deploy tamagotchi
-        let (address, _) = ProgramGenerator::create_program_with_gas_for_reply(
+    async fn create_tamagotchi(&mut self, owner: &ActorId, name: &String, gas: Option<GasConfig>) {
+        let gas = gas.unwrap_or(self.gas);
+        assert!(gas.is_within_bounds(), "Gas is out of bounds");
+
+        // deploy tamagotchi, the failed sending is reported like the failed initialization
+        let result = match ProgramGenerator::create_program_with_gas_for_reply(
             self.tmg_code_id,
             name,
-            GAS_FOR_CREATION,
+            gas.creation,
             0
-        )
-        .expect("Error during tamagotchi initialization")
-        .await
-        .expect("Program was not initialized");
+        ) {
+            Ok(future) => future.await,
+            Err(error) => Err(error),
+        };
+        let Ok((address, _)) = result else {
+            msg::reply(ArmyEvent::TamagotchiCreationFailed, 0)
+                .expect("Error during a reply `ArmyEvent::TamagotchiCreationFailed`");
+            return;
+        };
 
         // sending transfer event
         let transfer_to: ActorId = *owner;
-        let result = msg::send_for_reply_as::<_, TmgEvent>(
+        let result = msg::send_with_gas_for_reply_as::<_, TmgEvent>(
             address,
             TmgAction::Transfer(transfer_to),
+            gas.reply,
             0,
         )
         .expect("Error in sending a message `TmgAction::Transfer`")
@@ -60,6 +68,15 @@ impl Army for TmgArmy {
             }
         }
     }
+
+    fn set_gas(&mut self, gas: GasConfig) {
+        assert_eq!(msg::source(), self.owner, "Only the owner can set the gas");
+        assert!(gas.is_within_bounds(), "Gas is out of bounds");
+
+        self.gas = gas;
+        msg::reply(ArmyEvent::GasUpdated(gas), 0)
+            .expect("Error during a reply `ArmyEvent::GasUpdated`");
+    }
 }
 
 #[gstd::async_main]
@@ -69,7 +86,8 @@ async fn main() {
     
     let sender = msg::source();
     match action {
-        ArmyAction::CreateTamagotchi(name) => factory.create_tamagotchi(&sender, &name).await,
+        ArmyAction::CreateTamagotchi { name, gas } => factory.create_tamagotchi(&sender, &name, gas).await,
+        ArmyAction::SetGas(gas) => factory.set_gas(gas),
     }
 }
 
@@ -79,6 +97,7 @@ extern "C" fn init() {
         msg::load().expect("Unable to decode CodeId of the Tamagotchi program");
     let tamagotchi_army = TmgArmy {
         tmg_code_id,
+        owner: msg::source(),
         ..Default::default()
     };
     unsafe { TAMAGOTCHI_ARMY = Some(tamagotchi_army) };
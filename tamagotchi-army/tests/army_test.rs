@@ -1,4 +1,4 @@
-use gtest::{Program, System};
+use gtest::{Log, Program, System};
 use tmg_army_io::*;
 
 const PROGRAM_ID: u64 = 1;
@@ -26,7 +26,7 @@ fn create_tamagotchi() {
 
     // create tamagotchi #1
     let mut name = "tmg1".to_string();
-    let mut res = program.send(BUYER_1, ArmyAction::CreateTamagotchi(name));
+    let mut res = program.send(BUYER_1, ArmyAction::CreateTamagotchi { name, gas: None });
     let mut state: TmgArmy = program.read_state().expect("Error while reading the state");
     
     assert!(!res.main_failed());
@@ -35,10 +35,71 @@ fn create_tamagotchi() {
 
     // create tamagotchi #2
     name = "tmg2".to_string();
-    res = program.send(BUYER_2, ArmyAction::CreateTamagotchi(name));
+    res = program.send(BUYER_2, ArmyAction::CreateTamagotchi { name, gas: None });
     state = program.read_state().expect("Error while reading the state");
 
     assert!(!res.main_failed());
     assert!(!res.log().is_empty());
     assert!(state.tmg_number == 2);
-}
\ No newline at end of file
+}
+#[test]
+fn creation_gas() {
+    let sys = System::new();
+
+    init_tmg_army(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+
+    let gas = GasConfig {
+        creation: 3_000_000_000,
+        reply: DEFAULT_GAS_FOR_REPLY,
+    };
+
+    // must fail since only the owner can set the gas
+    let res = program.send(BUYER_1, ArmyAction::SetGas(gas));
+    assert!(res.main_failed());
+
+    let res = program.send(OWNER, ArmyAction::SetGas(gas));
+    let log = Log::builder()
+        .dest(OWNER)
+        .payload(ArmyEvent::GasUpdated(gas));
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    // must fail since the override exceeds the bounds
+    let res = program.send(BUYER_1, ArmyAction::CreateTamagotchi {
+        name: "tmg1".to_string(),
+        gas: Some(GasConfig {
+            creation: MAX_GAS + 1,
+            reply: DEFAULT_GAS_FOR_REPLY,
+        }),
+    });
+    assert!(res.main_failed());
+
+    let state: TmgArmy = program.read_state().expect("Error while reading the state");
+    assert_eq!(state.gas, gas);
+}
+
+#[test]
+fn low_creation_gas() {
+    let sys = System::new();
+
+    init_tmg_army(&sys);
+    let program = sys.get_program(PROGRAM_ID);
+
+    // the gas is within the bounds, but not enough to initialize the tamagotchi
+    let res = program.send(BUYER_1, ArmyAction::CreateTamagotchi {
+        name: "tmg1".to_string(),
+        gas: Some(GasConfig {
+            creation: MIN_GAS,
+            reply: DEFAULT_GAS_FOR_REPLY,
+        }),
+    });
+    let log = Log::builder()
+        .dest(BUYER_1)
+        .payload(ArmyEvent::TamagotchiCreationFailed);
+    assert!(!res.main_failed());
+    assert!(res.contains(&log));
+
+    let state: TmgArmy = program.read_state().expect("Error while reading the state");
+    assert_eq!(state.tmg_number, 0);
+}
@@ -0,0 +1,92 @@
+#![no_std]
+use codec::{Decode, Encode};
+use gmeta::{In, InOut, Metadata};
+use gstd::{prelude::*, ActorId};
+use scale_info::TypeInfo;
+
+pub type TamagotchiId = ActorId;
+
+pub struct ProgramMetadata;
+
+impl Metadata for ProgramMetadata {
+    type Init = In<()>;
+    type Reply = ();
+    type Others = ();
+    type Signal = ();
+    type Handle = InOut<AuctionAction, AuctionEvent>;
+    type State = AuctionHouse;
+}
+
+#[derive(Default, Encode, Decode, TypeInfo)]
+pub struct AuctionHouse {
+    pub auctions: BTreeMap<TamagotchiId, Auction>,
+}
+
+#[derive(Debug, Clone, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub struct Auction {
+    pub seller: ActorId,
+    pub starting_price: u128,
+    /// The lowest bid the seller agrees to sell for.
+    pub reserve_price: u128,
+    /// Every next bid must exceed the highest one at least by this amount.
+    /// If it's zero, the next bid still must be greater than the highest one.
+    pub min_increment: u128,
+    pub end_block: u32,
+    pub highest_bidder: Option<ActorId>,
+    pub highest_bid: u128,
+    pub status: AuctionStatus,
+}
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum AuctionStatus {
+    /// The auction is waiting for the tamagotchi to be transferred.
+    Pending,
+    Active,
+    /// The tamagotchi is being transferred to the winner or back to the seller.
+    Settling,
+}
+
+#[derive(Encode, Decode, TypeInfo)]
+pub enum AuctionAction {
+    /// Takes custody of the tamagotchi and starts the auction.
+    /// The sender must own the tamagotchi and approve the auction program
+    /// with `TmgAction::Approve` beforehand.
+    StartAuction {
+        tamagotchi_id: TamagotchiId,
+        starting_price: u128,
+        reserve_price: u128,
+        min_increment: u128,
+        duration: u32,
+    },
+    /// Bids the attached value.
+    Bid(TamagotchiId),
+    /// Closes the auction after its end block.
+    /// It's sent by the program itself as a delayed message.
+    /// If the tamagotchi transfer fails, it's retried while the gas lasts,
+    /// and anyone can retry it by sending `Settle` again.
+    Settle(TamagotchiId),
+}
+
+#[derive(Debug, PartialEq, Eq, Encode, Decode, TypeInfo)]
+pub enum AuctionEvent {
+    AuctionStarted {
+        tamagotchi_id: TamagotchiId,
+        end_block: u32,
+    },
+    BidPlaced {
+        tamagotchi_id: TamagotchiId,
+        bidder: ActorId,
+        amount: u128,
+    },
+    Outbid {
+        tamagotchi_id: TamagotchiId,
+        amount: u128,
+    },
+    AuctionSettled {
+        tamagotchi_id: TamagotchiId,
+        winner: Option<ActorId>,
+        price: u128,
+    },
+    NotOwner(TamagotchiId),
+    TransferFailed(TamagotchiId),
+}
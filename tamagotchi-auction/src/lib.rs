@@ -0,0 +1,305 @@
+#![no_std]
+use gstd::{exec, msg, prelude::*, ActorId};
+use async_trait::async_trait;
+use tmg_auction_io::*;
+use hello_world_io::{TmgAction, TmgEvent};
+
+pub const GAS_FOR_SETTLEMENT: u64 = 5_000_000_000;
+/// The delayed closing can retry the failed transfer this many times.
+pub const SETTLEMENT_RETRIES: u64 = 2;
+const GAS_FOR_RESCHEDULING: u64 = 1_000_000_000;
+const SETTLEMENT_RETRY_DELAY: u32 = 1;
+
+static mut AUCTION_HOUSE: Option<AuctionHouse> = None;
+
+#[async_trait]
+pub trait TmgAuction {
+    async fn start_auction(
+        &mut self,
+        tamagotchi_id: &TamagotchiId,
+        starting_price: u128,
+        reserve_price: u128,
+        min_increment: u128,
+        duration: u32,
+    );
+    fn bid(&mut self, tamagotchi_id: &TamagotchiId);
+    async fn settle(&mut self, tamagotchi_id: &TamagotchiId);
+}
+
+#[async_trait]
+impl TmgAuction for AuctionHouse {
+    async fn start_auction(
+        &mut self,
+        tamagotchi_id: &TamagotchiId,
+        starting_price: u128,
+        reserve_price: u128,
+        min_increment: u128,
+        duration: u32,
+    ) {
+        assert!(
+            !self.auctions.contains_key(tamagotchi_id),
+            "The tamagotchi is already on auction"
+        );
+        assert!(duration > 0, "Duration must be greater than zero");
+
+        // reserving the auction slot while waiting for the tamagotchi
+        let seller = msg::source();
+        self.auctions.insert(
+            *tamagotchi_id,
+            Auction {
+                seller,
+                starting_price,
+                reserve_price,
+                min_increment,
+                end_block: 0,
+                highest_bidder: None,
+                highest_bid: 0,
+                status: AuctionStatus::Pending,
+            },
+        );
+
+        // checking the ownership
+        let result = msg::send_for_reply_as::<_, TmgEvent>(
+            *tamagotchi_id,
+            TmgAction::Owner,
+            0,
+        )
+        .expect("Error in sending a message `TmgAction::Owner`")
+        .await;
+
+        // state changes made before the await would survive a panic,
+        // so the failure is reported with a reply
+        if !matches!(result, Ok(TmgEvent::Owner(owner)) if owner == seller) {
+            self.auctions.remove(tamagotchi_id);
+            msg::reply(AuctionEvent::NotOwner(*tamagotchi_id), 0)
+                .expect("Error during a reply `AuctionEvent::NotOwner`");
+            return;
+        }
+
+        // taking custody of the tamagotchi
+        if !transfer_tamagotchi(tamagotchi_id, &exec::program_id()).await {
+            self.auctions.remove(tamagotchi_id);
+            msg::reply(AuctionEvent::TransferFailed(*tamagotchi_id), 0)
+                .expect("Error during a reply `AuctionEvent::TransferFailed`");
+            return;
+        }
+
+        let end_block = exec::block_height().saturating_add(duration);
+        let auction = self
+            .auctions
+            .get_mut(tamagotchi_id)
+            .expect("The auction doesn't exist");
+        auction.end_block = end_block;
+        auction.status = AuctionStatus::Active;
+
+        // scheduling the auction closing, with the gas for the retries
+        msg::send_with_gas_delayed(
+            exec::program_id(),
+            AuctionAction::Settle(*tamagotchi_id),
+            GAS_FOR_SETTLEMENT * (SETTLEMENT_RETRIES + 1),
+            0,
+            duration,
+        )
+        .expect("Error while sending delayed.");
+
+        msg::reply(
+            AuctionEvent::AuctionStarted {
+                tamagotchi_id: *tamagotchi_id,
+                end_block,
+            },
+            0,
+        )
+        .expect("Error during a reply `AuctionEvent::AuctionStarted`");
+    }
+
+    fn bid(&mut self, tamagotchi_id: &TamagotchiId) {
+        let auction = self
+            .auctions
+            .get_mut(tamagotchi_id)
+            .expect("The auction doesn't exist");
+        assert_eq!(
+            auction.status,
+            AuctionStatus::Active,
+            "The auction is not active"
+        );
+        assert!(
+            exec::block_height() < auction.end_block,
+            "The auction is over"
+        );
+
+        let bidder = msg::source();
+        let amount = msg::value();
+        assert_ne!(bidder, auction.seller, "Seller can't bid");
+
+        // the bid must beat the leader even without the increment
+        let min_bid = match auction.highest_bidder {
+            Some(_) => auction
+                .highest_bid
+                .saturating_add(auction.min_increment.max(1)),
+            None => auction.starting_price,
+        };
+        assert!(amount >= min_bid, "The bid is too low");
+
+        // refunding the outbid bidder
+        if let Some(prev_bidder) = auction.highest_bidder {
+            msg::send(
+                prev_bidder,
+                AuctionEvent::Outbid {
+                    tamagotchi_id: *tamagotchi_id,
+                    amount: auction.highest_bid,
+                },
+                auction.highest_bid,
+            )
+            .expect("Error in sending a refund to the outbid bidder");
+        }
+
+        auction.highest_bidder = Some(bidder);
+        auction.highest_bid = amount;
+
+        msg::reply(
+            AuctionEvent::BidPlaced {
+                tamagotchi_id: *tamagotchi_id,
+                bidder,
+                amount,
+            },
+            0,
+        )
+        .expect("Error during a reply `AuctionEvent::BidPlaced`");
+    }
+
+    async fn settle(&mut self, tamagotchi_id: &TamagotchiId) {
+        let auction = self
+            .auctions
+            .get_mut(tamagotchi_id)
+            .expect("The auction doesn't exist");
+        assert_eq!(
+            auction.status,
+            AuctionStatus::Active,
+            "The auction is not active"
+        );
+        assert!(
+            exec::block_height() >= auction.end_block,
+            "The auction is not over yet"
+        );
+        auction.status = AuctionStatus::Settling;
+
+        // the tamagotchi goes back to the seller if the reserve price is not met
+        let winner = auction
+            .highest_bidder
+            .filter(|_| auction.highest_bid >= auction.reserve_price);
+        let seller = auction.seller;
+        let new_owner = winner.unwrap_or(seller);
+
+        if !transfer_tamagotchi(tamagotchi_id, &new_owner).await {
+            let auction = self
+                .auctions
+                .get_mut(tamagotchi_id)
+                .expect("The auction doesn't exist");
+            auction.status = AuctionStatus::Active;
+
+            // retrying with the gas left, anyone can also retry with `Settle`
+            let gas = exec::gas_available().saturating_sub(GAS_FOR_RESCHEDULING);
+            if gas >= GAS_FOR_SETTLEMENT {
+                msg::send_with_gas_delayed(
+                    exec::program_id(),
+                    AuctionAction::Settle(*tamagotchi_id),
+                    gas,
+                    0,
+                    SETTLEMENT_RETRY_DELAY,
+                )
+                .expect("Error while sending delayed.");
+            }
+            reply_if_external(AuctionEvent::TransferFailed(*tamagotchi_id));
+            return;
+        }
+
+        let auction = self
+            .auctions
+            .remove(tamagotchi_id)
+            .expect("The auction doesn't exist");
+        let price = if winner.is_some() { auction.highest_bid } else { 0 };
+        let event = AuctionEvent::AuctionSettled {
+            tamagotchi_id: *tamagotchi_id,
+            winner,
+            price,
+        };
+
+        // sharing funds: to the seller if sold, back to the bidder otherwise
+        msg::send(seller, &event, price).expect("Error in sending funds to the seller");
+        if let (None, Some(bidder)) = (winner, auction.highest_bidder) {
+            msg::send(bidder, &event, auction.highest_bid)
+                .expect("Error in sending a refund to the bidder");
+        }
+
+        reply_if_external(event);
+    }
+}
+
+#[gstd::async_main]
+async fn main() {
+    let action: AuctionAction = msg::load().expect("Unable to decode `AuctionAction`");
+    let auction_house = unsafe { AUCTION_HOUSE.get_or_insert(Default::default()) };
+
+    match action {
+        AuctionAction::StartAuction {
+            tamagotchi_id,
+            starting_price,
+            reserve_price,
+            min_increment,
+            duration,
+        } => {
+            auction_house
+                .start_auction(
+                    &tamagotchi_id,
+                    starting_price,
+                    reserve_price,
+                    min_increment,
+                    duration,
+                )
+                .await
+        }
+        AuctionAction::Bid(tamagotchi_id) => auction_house.bid(&tamagotchi_id),
+        AuctionAction::Settle(tamagotchi_id) => auction_house.settle(&tamagotchi_id).await,
+    }
+}
+
+#[no_mangle]
+extern "C" fn init() {
+    unsafe { AUCTION_HOUSE = Some(Default::default()) };
+}
+
+#[no_mangle]
+extern "C" fn state() {
+   let auction_house = unsafe {
+    AUCTION_HOUSE.as_ref().expect("The contract is not initialized")
+   };
+
+   msg::reply(auction_house, 0).expect("Failed to share state");
+}
+
+#[no_mangle]
+// It returns the Hash of metadata.
+// .metahash is generating automatically while you are using build.rs
+extern "C" fn metahash() {
+   let metahash: [u8; 32] = include!("../.metahash");
+   msg::reply(metahash, 0).expect("Failed to share metahash");
+}
+
+async fn transfer_tamagotchi(tamagotchi_id: &TamagotchiId, to: &ActorId) -> bool {
+    let result = msg::send_for_reply_as::<_, TmgEvent>(
+        *tamagotchi_id,
+        TmgAction::Transfer(*to),
+        0,
+    )
+    .expect("Error in sending a message `TmgAction::Transfer`")
+    .await;
+
+    matches!(result, Ok(TmgEvent::Transfer(_)))
+}
+
+// the delayed `Settle` is sent by the program itself and needs no reply
+fn reply_if_external(event: AuctionEvent) {
+    if msg::source() != exec::program_id() {
+        msg::reply(event, 0).expect("Error during a reply `AuctionEvent`");
+    }
+}
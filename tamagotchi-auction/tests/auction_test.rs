@@ -0,0 +1,210 @@
+use gtest::{Log, Program, System};
+use hello_world_io::{Tamagotchi, TmgAction, TmgInit};
+use tmg_auction_io::*;
+
+const AUCTION_ID: u64 = 1;
+const TAMAGOTCHI_ID: u64 = 2;
+const SELLER: u64 = 100;
+const BIDDER_1: u64 = 101;
+const BIDDER_2: u64 = 102;
+const DURATION: u32 = 10;
+
+fn init_auction(sys: &System) {
+    sys.init_logger();
+    let auction = Program::current(&sys);
+    let res = auction.send_bytes(SELLER, []);
+    assert!(!res.main_failed());
+
+    let tamagotchi = Program::from_file(
+        &sys,
+        "../hello-world/target/wasm32-unknown-unknown/debug/hello_world.opt.wasm",
+    );
+    let res = tamagotchi.send(SELLER, TmgInit {
+        name: String::from("Satoshi"),
+        royalty_bps: None,
+    });
+    assert!(!res.main_failed());
+
+    // approve the auction to take custody of the tamagotchi
+    let res = tamagotchi.send(SELLER, TmgAction::Approve(AUCTION_ID.into()));
+    assert!(!res.main_failed());
+}
+
+fn start_auction(sys: &System, reserve_price: u128) {
+    let auction = sys.get_program(AUCTION_ID);
+    let res = auction.send(SELLER, AuctionAction::StartAuction {
+        tamagotchi_id: TAMAGOTCHI_ID.into(),
+        starting_price: 500,
+        reserve_price,
+        min_increment: 100,
+        duration: DURATION,
+    });
+    assert!(!res.main_failed());
+
+    let state: AuctionHouse = auction.read_state().expect("Error while reading the state");
+    let auction = state
+        .auctions
+        .get(&TAMAGOTCHI_ID.into())
+        .expect("The auction doesn't exist");
+    assert_eq!(auction.status, AuctionStatus::Active);
+}
+
+fn tamagotchi_owner(sys: &System) -> gstd::ActorId {
+    let tamagotchi = sys.get_program(TAMAGOTCHI_ID);
+    let state: Tamagotchi = tamagotchi.read_state().expect("Error while reading the state");
+    state.owner
+}
+
+#[test]
+fn start_auction_failures() {
+    // initialize env
+    let sys = System::new();
+
+    init_auction(&sys);
+    let auction = sys.get_program(AUCTION_ID);
+
+    // must fail since BIDDER_1 is not owner
+    let res = auction.send(BIDDER_1, AuctionAction::StartAuction {
+        tamagotchi_id: TAMAGOTCHI_ID.into(),
+        starting_price: 500,
+        reserve_price: 1000,
+        min_increment: 100,
+        duration: DURATION,
+    });
+    let expected_log = Log::builder()
+        .dest(BIDDER_1)
+        .payload(AuctionEvent::NotOwner(TAMAGOTCHI_ID.into()));
+    assert!(res.contains(&expected_log));
+
+    let state: AuctionHouse = auction.read_state().expect("Error while reading the state");
+    assert!(state.auctions.is_empty());
+    assert!(tamagotchi_owner(&sys) == SELLER.into());
+}
+
+#[test]
+fn auction_sold() {
+    // initialize env
+    let sys = System::new();
+
+    init_auction(&sys);
+    start_auction(&sys, 1000);
+    let auction = sys.get_program(AUCTION_ID);
+
+    // the auction takes custody of the tamagotchi
+    assert!(tamagotchi_owner(&sys) == AUCTION_ID.into());
+
+    sys.mint_to(BIDDER_1, 2000);
+    sys.mint_to(BIDDER_2, 2000);
+
+    // must fail since the bid is less than the starting price
+    let res = auction.send_with_value(BIDDER_1, AuctionAction::Bid(TAMAGOTCHI_ID.into()), 400);
+    assert!(res.main_failed());
+
+    // successful bid
+    let res = auction.send_with_value(BIDDER_1, AuctionAction::Bid(TAMAGOTCHI_ID.into()), 500);
+    let expected_log = Log::builder()
+        .dest(BIDDER_1)
+        .payload(AuctionEvent::BidPlaced {
+            tamagotchi_id: TAMAGOTCHI_ID.into(),
+            bidder: BIDDER_1.into(),
+            amount: 500,
+        });
+    assert!(!res.main_failed());
+    assert!(res.contains(&expected_log));
+
+    // must fail since the increment is less than the minimum
+    let res = auction.send_with_value(BIDDER_2, AuctionAction::Bid(TAMAGOTCHI_ID.into()), 550);
+    assert!(res.main_failed());
+
+    // successful bid, BIDDER_1 gets the refund
+    let res = auction.send_with_value(BIDDER_2, AuctionAction::Bid(TAMAGOTCHI_ID.into()), 1200);
+    let expected_log = Log::builder()
+        .dest(BIDDER_1)
+        .payload(AuctionEvent::Outbid {
+            tamagotchi_id: TAMAGOTCHI_ID.into(),
+            amount: 500,
+        });
+    assert!(!res.main_failed());
+    assert!(res.contains(&expected_log));
+
+    sys.claim_value_from_mailbox(BIDDER_1);
+    assert_eq!(sys.balance_of(BIDDER_1), 2000);
+
+    // the auction is closed by the delayed message
+    sys.spend_blocks(DURATION);
+
+    let state: AuctionHouse = auction.read_state().expect("Error while reading the state");
+    assert!(state.auctions.is_empty());
+    assert!(tamagotchi_owner(&sys) == BIDDER_2.into());
+
+    // must fail since the auction is over
+    let res = auction.send_with_value(BIDDER_1, AuctionAction::Bid(TAMAGOTCHI_ID.into()), 1500);
+    assert!(res.main_failed());
+
+    // claim value for the seller
+    sys.claim_value_from_mailbox(SELLER);
+    assert_eq!(sys.balance_of(SELLER), 1200);
+}
+
+#[test]
+fn auction_reserve_not_met() {
+    // initialize env
+    let sys = System::new();
+
+    init_auction(&sys);
+    start_auction(&sys, 1000);
+    let auction = sys.get_program(AUCTION_ID);
+
+    sys.mint_to(BIDDER_1, 2000);
+
+    let res = auction.send_with_value(BIDDER_1, AuctionAction::Bid(TAMAGOTCHI_ID.into()), 700);
+    assert!(!res.main_failed());
+
+    // must fail since the auction is not over yet
+    let res = auction.send(SELLER, AuctionAction::Settle(TAMAGOTCHI_ID.into()));
+    assert!(res.main_failed());
+
+    sys.spend_blocks(DURATION);
+
+    // the tamagotchi goes back to the seller and the bid is refunded
+    assert!(tamagotchi_owner(&sys) == SELLER.into());
+    sys.claim_value_from_mailbox(BIDDER_1);
+    assert_eq!(sys.balance_of(BIDDER_1), 2000);
+}
+
+#[test]
+fn equal_bid_with_zero_increment() {
+    // initialize env
+    let sys = System::new();
+
+    init_auction(&sys);
+    let auction = sys.get_program(AUCTION_ID);
+    let res = auction.send(SELLER, AuctionAction::StartAuction {
+        tamagotchi_id: TAMAGOTCHI_ID.into(),
+        starting_price: 500,
+        reserve_price: 1000,
+        min_increment: 0,
+        duration: DURATION,
+    });
+    assert!(!res.main_failed());
+
+    sys.mint_to(BIDDER_1, 2000);
+    sys.mint_to(BIDDER_2, 2000);
+
+    let res = auction.send_with_value(BIDDER_1, AuctionAction::Bid(TAMAGOTCHI_ID.into()), 500);
+    assert!(!res.main_failed());
+
+    // must fail since the bid doesn't beat the leader
+    let res = auction.send_with_value(BIDDER_2, AuctionAction::Bid(TAMAGOTCHI_ID.into()), 500);
+    assert!(res.main_failed());
+
+    let res = auction.send_with_value(BIDDER_2, AuctionAction::Bid(TAMAGOTCHI_ID.into()), 501);
+    assert!(!res.main_failed());
+
+    let state: AuctionHouse = auction.read_state().expect("Error while reading the state");
+    let auction = state
+        .auctions
+        .get(&TAMAGOTCHI_ID.into())
+        .expect("The auction doesn't exist");
+    assert_eq!(auction.highest_bidder, Some(BIDDER_2.into()));
+}